@@ -0,0 +1,336 @@
+use std::str::FromStr;
+
+use mancala_full_search::{
+    index_size, Allocation, Capture, Difficulty, Kalah, Notation, Oware, Ruleset, Side, Variant,
+    WinCondition,
+};
+
+pub const USAGE: &str = "\
+usage: mancala <command> [options]
+
+commands:
+    solve     勝敗と終了までの手数を全探索する
+    score     スコアルールで最善のスコア差を全探索する
+    play      探索結果を見ながら対戦する
+    analyze   初期盤面の各手の評価を表示する
+    count     到達可能な盤面数を数える
+    table     穴の数と石の数ごとの結果を表にする
+    tablebase 石の少ない盤面の評価値の表を作る
+    handicap  先手の石を何個取り除けばスコアの差がなくなるか探す
+    review    棋譜の各手番の評価値と最善手を表示する
+    engine    標準入出力で newgame, position, go, eval, quit のコマンドを受け付ける
+
+options:
+    --pits <N>        穴の数 (1-8, default: 5)
+    --seeds <N>       穴1つあたりの石の数 (1-8, default: 3)
+    --position <NOTATION>
+                      初期盤面を `3,3,3/3,3,3 0-0 F` の形で指定する
+                      (先手の穴/後手の穴 先手のストア-後手のストア 手番)。
+                      --pits と --seeds は盤面から決める
+    --rules <RULES>   kalah: カラハ (default), oware: オワリ
+    --stealing        kalah: 石取りあり
+    --no-stealing     kalah: 石取りなし (default)
+    --capture-empty   kalah: 石取りありで、向かいが空でも最後の石を取る
+    --relay           kalah: 最後の石が石のあった穴に入ったら、その穴の石を取り出して蒔き続ける
+    --win <RULE>      勝利条件 (score: スコア性, empty: 早なくし, misere: スコアが小さいほうが勝ち)
+                      default: solve と play は empty、それ以外は score
+    --allocation <RULE>
+                      終了時に穴に残った石 (owner: その側 (default), emptied: 先に石をなくした側,
+                      opponent: 先に石をなくした側の相手)
+    --threads <N>     探索スレッド数 (default: 4)
+    --div <N>         ハッシュテーブルの分割数 (default: 1024)
+    --compress <N>    score: 結果を指定した深さごとに間引いて数える
+    --dense           score: ハッシュテーブルの代わりに盤面の番号を添字にした配列を使う
+    --retrograde      score: 石の少ない盤面から後ろ向きに全ての配置を解く
+    --alphabeta       score, handicap: 置換表を使った αβ 探索で初期盤面の値だけを求める
+    --tt-size <N>     score: --alphabeta の置換表のエントリ数 (default: 1048576)
+    --pie             score, table: 後手が最初の手番で席を入れ替えられるパイルールでの値も求める
+    --tablebase <PATH>
+                      score: 石の少ない盤面は表を引く。--output とは同時に使えない
+                      tablebase: 既存の表を広げる
+    --tb-seeds <N>    tablebase: 穴に残っている石の最大数
+    --output <PATH>   solve, score, tablebase: 探索結果をファイルに保存する
+    --db <PATH>       play, analyze, engine: 探索せずに保存した結果を読み込む
+    --color           play: 手番側の段を端末の色で強調する
+    --record <PATH>   play: 終わった対局の棋譜をファイルに追記する
+                      review: 読み込む棋譜のファイル。--pits, --seeds, 石取りの有無, --win と
+                      --allocation は棋譜から決める
+    --side <SIDE>     play: 人が指す側 (first, second)。もう一方はコンピュータが指す
+                      指定しなければ両方を人が指す
+    --level <LEVEL>   play: コンピュータが最善でない手番を選ぶ確率
+                      (easy: 0.5, normal: 0.2, hard: 0.05, perfect: 0 (default) か 0 から 1 の数)
+    --seed <N>        play: コンピュータの乱数の種 (default: 時刻から決める)
+
+`table` では --pits と --seeds はそれぞれの最大値として扱い、--pie ならパイルールでの値を表にする";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Command {
+    Solve,
+    Score,
+    Play,
+    Analyze,
+    Count,
+    Table,
+    Tablebase,
+    Handicap,
+    Review,
+    Engine,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "solve" => Ok(Command::Solve),
+            "score" => Ok(Command::Score),
+            "play" => Ok(Command::Play),
+            "analyze" => Ok(Command::Analyze),
+            "count" => Ok(Command::Count),
+            "table" => Ok(Command::Table),
+            "tablebase" => Ok(Command::Tablebase),
+            "handicap" => Ok(Command::Handicap),
+            "review" => Ok(Command::Review),
+            "engine" => Ok(Command::Engine),
+            _ => Err(format!("不明なコマンドです: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Rules {
+    Kalah,
+    Oware,
+}
+
+impl FromStr for Rules {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "kalah" => Ok(Rules::Kalah),
+            "oware" => Ok(Rules::Oware),
+            _ => Err(format!("不明なルールです: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub command: Command,
+    pub pits: usize,
+    pub seeds: usize,
+    pub position: Option<Notation>,
+    pub rules: Rules,
+    pub capture: Capture,
+    pub relay: bool,
+    pub win: Option<WinCondition>,
+    pub allocation: Allocation,
+    pub threads: usize,
+    pub div: usize,
+    pub compress: Option<usize>,
+    pub dense: bool,
+    pub retrograde: bool,
+    pub alphabeta: bool,
+    pub tt_size: usize,
+    pub pie: bool,
+    pub tablebase: Option<String>,
+    pub tb_seeds: Option<usize>,
+    pub output: Option<String>,
+    pub db: Option<String>,
+    pub color: bool,
+    pub record: Option<String>,
+    pub side: Option<Side>,
+    pub level: Difficulty,
+    pub seed: Option<u64>,
+}
+
+fn parse_value<T, I>(name: &str, args: &mut I) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: ToString,
+    I: Iterator<Item = String>,
+{
+    let value = args
+        .next()
+        .ok_or_else(|| format!("{} には値が必要です", name))?;
+    value
+        .parse()
+        .map_err(|e: T::Err| format!("{}: {}", name, e.to_string()))
+}
+
+impl Config {
+    pub fn parse<I>(args: I) -> Result<Config, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter();
+        let command = args.next().ok_or_else(|| USAGE.to_string())?.parse()?;
+        let mut config = Config {
+            command,
+            pits: 5,
+            seeds: 3,
+            position: None,
+            rules: Rules::Kalah,
+            capture: Capture::None,
+            relay: false,
+            win: None,
+            allocation: Allocation::Owner,
+            threads: 4,
+            div: 1024,
+            compress: None,
+            dense: false,
+            retrograde: false,
+            alphabeta: false,
+            tt_size: 1 << 20,
+            pie: false,
+            tablebase: None,
+            tb_seeds: None,
+            output: None,
+            db: None,
+            color: false,
+            record: None,
+            side: None,
+            level: Difficulty { mistake: 0.0 },
+            seed: None,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--pits" => config.pits = parse_value(&arg, &mut args)?,
+                "--seeds" => config.seeds = parse_value(&arg, &mut args)?,
+                "--position" => config.position = Some(parse_value(&arg, &mut args)?),
+                "--rules" => config.rules = parse_value(&arg, &mut args)?,
+                "--stealing" => config.capture = Capture::Opposite,
+                "--no-stealing" => config.capture = Capture::None,
+                "--capture-empty" => config.capture = Capture::Always,
+                "--relay" => config.relay = true,
+                "--win" => config.win = Some(parse_value(&arg, &mut args)?),
+                "--allocation" => config.allocation = parse_value(&arg, &mut args)?,
+                "--threads" => config.threads = parse_value(&arg, &mut args)?,
+                "--div" => config.div = parse_value(&arg, &mut args)?,
+                "--compress" => config.compress = Some(parse_value(&arg, &mut args)?),
+                "--dense" => config.dense = true,
+                "--retrograde" => config.retrograde = true,
+                "--alphabeta" => config.alphabeta = true,
+                "--tt-size" => config.tt_size = parse_value(&arg, &mut args)?,
+                "--pie" => config.pie = true,
+                "--tablebase" => config.tablebase = Some(parse_value(&arg, &mut args)?),
+                "--tb-seeds" => config.tb_seeds = Some(parse_value(&arg, &mut args)?),
+                "--output" => config.output = Some(parse_value(&arg, &mut args)?),
+                "--db" => config.db = Some(parse_value(&arg, &mut args)?),
+                "--color" => config.color = true,
+                "--record" => config.record = Some(parse_value(&arg, &mut args)?),
+                "--side" => config.side = Some(parse_value(&arg, &mut args)?),
+                "--level" => config.level = parse_value(&arg, &mut args)?,
+                "--seed" => config.seed = Some(parse_value(&arg, &mut args)?),
+                "-h" | "--help" => return Err(USAGE.to_string()),
+                _ => return Err(format!("不明なオプションです: {}\n\n{}", arg, USAGE)),
+            }
+        }
+        if let Some(ref position) = config.position {
+            // 石の合計が初期配置の合計に収まる最小の石の数にする
+            let pits = position.pits[0].len();
+            config.pits = pits;
+            config.seeds = position.seeds().div_ceil(2 * pits).max(1);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 盤面のルール
+    pub fn variant(&self) -> Variant {
+        match self.rules {
+            Rules::Kalah => Variant::Kalah(Kalah {
+                capture: self.capture,
+                relay: self.relay,
+            }),
+            Rules::Oware => Variant::Oware(Oware),
+        }
+    }
+
+    /// 穴の数、石の数と石取りの有無を変えた設定
+    pub fn for_game(&self, pits: usize, seeds: usize, stealing: bool) -> Result<Config, String> {
+        let mut config = self.clone();
+        config.pits = pits;
+        config.seeds = seeds;
+        if config.variant().stealing() != stealing {
+            config.capture = Capture::from_stealing(stealing);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 勝利条件。指定がなければ `solve` と `play` は早なくし、それ以外はスコア性
+    pub fn win(&self) -> WinCondition {
+        match (self.win, self.command) {
+            (Some(win), _) => win,
+            (None, Command::Solve) | (None, Command::Play) => WinCondition::Empty,
+            (None, _) => WinCondition::Score,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.pits == 0 || self.pits > 8 {
+            return Err("--pits は1から8の間で指定してください".to_string());
+        }
+        if self.seeds == 0 || self.seeds > 8 {
+            return Err("--seeds は1から8の間で指定してください".to_string());
+        }
+        if self.command != Command::Table && 2 * self.pits * self.seeds > i8::MAX as usize {
+            return Err(format!("石の総数は{}以下にしてください", i8::MAX));
+        }
+        if self.command != Command::Table
+            && index_size(self.pits, 2 * self.pits * self.seeds).is_none()
+        {
+            return Err("盤面の番号が64bitに収まりません".to_string());
+        }
+        if self.position.is_some() && self.command == Command::Table {
+            return Err("table では --position は使えません".to_string());
+        }
+        if self.command == Command::Review {
+            if self.record.is_none() {
+                return Err("review では --record で棋譜を指定してください".to_string());
+            }
+            if self.position.is_some() {
+                return Err("review では --position は使えません".to_string());
+            }
+        }
+        if self.tablebase.is_some() && self.compress.is_some() {
+            return Err("--tablebase と --compress は同時に使えません".to_string());
+        }
+        // 表を引いた盤面は結果に入らないので、保存しても読み込んだ側で値が足りない
+        if self.command == Command::Score && self.tablebase.is_some() && self.output.is_some() {
+            return Err("score では --tablebase と --output は同時に使えません".to_string());
+        }
+        if self.alphabeta
+            && (self.tablebase.is_some() || self.compress.is_some() || self.output.is_some())
+        {
+            return Err(
+                "--alphabeta は --tablebase, --compress, --output と同時に使えません".to_string(),
+            );
+        }
+        // 評価値と表のヘッダに収まる石の数まで
+        if self.tb_seeds.is_some_and(|seeds| seeds > i8::MAX as usize) {
+            return Err(format!("--tb-seeds は{}以下にしてください", i8::MAX));
+        }
+        if self.tt_size == 0 {
+            return Err("--tt-size は1以上にしてください".to_string());
+        }
+        if self.variant().may_repeat() && (self.retrograde || self.tablebase.is_some()) {
+            return Err(
+                "同じ盤面に戻ることがあるルールでは --retrograde と --tablebase は使えません"
+                    .to_string(),
+            );
+        }
+        if self.variant().may_repeat() && self.command == Command::Tablebase {
+            return Err("同じ盤面に戻ることがあるルールでは表を作れません".to_string());
+        }
+        if self.threads == 0 {
+            return Err("--threads は1以上にしてください".to_string());
+        }
+        if self.div == 0 {
+            return Err("--div は1以上にしてください".to_string());
+        }
+        Ok(())
+    }
+}
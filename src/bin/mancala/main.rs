@@ -0,0 +1,553 @@
+mod args;
+mod play;
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{stdin, stdout, BufReader, BufWriter};
+
+use generic_array::ArrayLength;
+use mancala_full_search::{
+    compress_dag, format_turn, index_size, parse_records, pie_score, save_db, search_clean,
+    search_handicap, search_mtdf, search_retrograde, search_score, search_score_dense,
+    search_score_with_tablebase, settlement_key, Board, CompactKey, DbKey, DbLookup, DbValue,
+    DynBoard, MappedDb, Position, Protocol, Record, Ruleset, ScoreDb, Settlement, Side, Tablebase,
+    TranspositionTable, Variant,
+};
+use typenum::*;
+
+use args::{Command, Config};
+use play::play;
+
+/// `$config.seeds` に対応する `Board<$p, S>` で `$f` を呼ぶ
+macro_rules! dispatch_seeds {
+    ($f:ident, $p:ty, $config:expr $(, $arg:expr)*) => {
+        match $config.seeds {
+            1 => $f::<$p, U1>($config $(, $arg)*),
+            2 => $f::<$p, U2>($config $(, $arg)*),
+            3 => $f::<$p, U3>($config $(, $arg)*),
+            4 => $f::<$p, U4>($config $(, $arg)*),
+            5 => $f::<$p, U5>($config $(, $arg)*),
+            6 => $f::<$p, U6>($config $(, $arg)*),
+            7 => $f::<$p, U7>($config $(, $arg)*),
+            8 => $f::<$p, U8>($config $(, $arg)*),
+            s => Err(format!("seeds={} には対応していません", s)),
+        }
+    };
+}
+
+/// `$config.pits` と `$config.seeds` に対応する `Board<P, S>` で `$f` を呼ぶ。
+/// `$arg` があれば `$config` の後に渡す
+macro_rules! dispatch {
+    ($f:ident, $config:expr $(, $arg:expr)*) => {
+        match $config.pits {
+            1 => dispatch_seeds!($f, U1, $config $(, $arg)*),
+            2 => dispatch_seeds!($f, U2, $config $(, $arg)*),
+            3 => dispatch_seeds!($f, U3, $config $(, $arg)*),
+            4 => dispatch_seeds!($f, U4, $config $(, $arg)*),
+            5 => dispatch_seeds!($f, U5, $config $(, $arg)*),
+            6 => dispatch_seeds!($f, U6, $config $(, $arg)*),
+            7 => dispatch_seeds!($f, U7, $config $(, $arg)*),
+            8 => dispatch_seeds!($f, U8, $config $(, $arg)*),
+            p => Err(format!("pits={} には対応していません", p)),
+        }
+    };
+}
+
+/// ルールを実行時に選ぶ盤面
+type GameBoard<P, S> = Board<P, S, Variant>;
+
+/// 設定に合わせた初期盤面。`--position` があればその盤面
+fn new_board<P, S>(config: &Config) -> GameBoard<P, S>
+where
+    P: ArrayLength<u8> + Clone,
+    S: Unsigned + Clone,
+{
+    let board = Board::with_rules(config.variant())
+        .with_win_condition(config.win())
+        .with_allocation(config.allocation);
+    match config.position {
+        // 穴の数と石の数は `Config::parse` で盤面に合わせてある
+        Some(ref position) => board.with_notation(position).unwrap(),
+        None => board,
+    }
+}
+
+fn save<B, K, V>(path: &str, board: &B, db: &HashMap<K, V>) -> Result<(), String>
+where
+    B: Position,
+    K: DbKey,
+    V: DbValue,
+{
+    let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+    save_db(BufWriter::new(file), board, db).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// 盤面 `B` の探索結果
+type Db<B, V> = Box<dyn DbLookup<<B as CompactKey>::Key, V>>;
+
+fn open<B, K, V>(path: &str, board: &B) -> Result<Box<dyn DbLookup<K, V>>, String>
+where
+    B: Position,
+    K: DbKey + 'static,
+    V: DbValue + 'static,
+{
+    let db = MappedDb::<K, V>::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    db.header()
+        .check(board)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    Ok(Box::new(db))
+}
+
+/// `db` から `key` の盤面の値を引く
+///
+/// `--db` の結果は保存したときの初期盤面から到達できる盤面しか持たないので、
+/// `--position` の盤面やその先の盤面がないことがある
+fn lookup<K, V>(db: &dyn DbLookup<K, V>, key: &K) -> Result<V, String> {
+    db.lookup(key)
+        .ok_or_else(|| "保存した探索結果にない盤面です。--db を外すと探索します".to_string())
+}
+
+/// `--db` があれば開き、なければ `search_clean` で探索する
+///
+/// キーは `settlement_key` で作る
+fn clean_db<P, S>(
+    config: &Config,
+    board: &GameBoard<P, S>,
+) -> Result<Box<dyn DbLookup<u128, Settlement>>, String>
+where
+    P: ArrayLength<u8> + Clone + Send,
+    S: Unsigned + Clone + Send,
+{
+    match config.db {
+        Some(ref path) => open(path, board),
+        None => {
+            let db: HashMap<_, _> = search_clean(board.clone(), config.threads, config.div).into();
+            Ok(Box::new(db))
+        }
+    }
+}
+
+/// `--db` があれば開き、なければ `search_score` で探索する
+fn score_db<P, S>(
+    config: &Config,
+    board: &GameBoard<P, S>,
+) -> Result<Db<GameBoard<P, S>, i8>, String>
+where
+    P: ArrayLength<u8> + Clone + Send,
+    S: Unsigned + Clone + Send,
+{
+    match config.db {
+        Some(ref path) => open(path, board),
+        None => {
+            let db: HashMap<_, _> = search_score(board.clone(), config.threads, config.div).into();
+            Ok(Box::new(db))
+        }
+    }
+}
+
+fn solve<P, S>(config: &Config) -> Result<(), String>
+where
+    P: ArrayLength<u8> + Clone + Send,
+    S: Unsigned + Clone + Send,
+{
+    let board = new_board::<P, S>(config);
+    let key = settlement_key(&board);
+    let (pits, seeds, stealing) = board.triple();
+    let db: HashMap<_, _> = search_clean(board.clone(), config.threads, config.div).into();
+    println!(
+        "{} pits={} seeds={} win={:?}",
+        stealing,
+        pits,
+        seeds,
+        board.win_condition()
+    );
+    println!("len={} score={}", db.len(), db.get(&key).unwrap());
+    if let Some(ref path) = config.output {
+        save(path, &board, &db)?;
+    }
+    Ok(())
+}
+
+fn score<P, S>(config: &Config) -> Result<(), String>
+where
+    P: ArrayLength<u8> + Clone + Send,
+    S: Unsigned + Clone + Send,
+{
+    let board = new_board::<P, S>(config);
+    let key = board.key();
+    let (pits, seeds, stealing) = board.triple();
+    if config.alphabeta {
+        let mut tt = TranspositionTable::new(config.tt_size);
+        let score = search_mtdf(&board, &mut tt, 0);
+        println!("{} pits={} seeds={}", stealing, pits, seeds);
+        println!("tt={} score={}", tt.len(), score);
+        if config.pie {
+            let pie = pie_score(&board, |b| search_mtdf(b, &mut tt, 0));
+            println!("pie={}", pie);
+        }
+        return Ok(());
+    }
+    let tablebase = match config.tablebase {
+        Some(ref path) => Some(open_tablebase(path, &board)?),
+        None => None,
+    };
+    let db: HashMap<_, _> = if let Some(ref tablebase) = tablebase {
+        search_score_with_tablebase(board.clone(), config.threads, config.div, tablebase).into()
+    } else if config.retrograde {
+        search_retrograde(board.clone(), config.threads).into()
+    } else if config.dense {
+        search_score_dense(board.clone(), config.threads).into()
+    } else {
+        search_score(board.clone(), config.threads, config.div).into()
+    };
+    println!("{} pits={} seeds={}", stealing, pits, seeds);
+    let score = db
+        .get(&key)
+        .cloned()
+        .or_else(|| tablebase.as_ref().and_then(|tb| tb.probe(&board)))
+        .unwrap();
+    // DB の値はストアの差を除いたもの
+    println!("len={} score={}", db.len(), board.store_score() + score);
+    if config.pie {
+        let pie = pie_score(&board, |b| {
+            let score = db
+                .get(&b.key())
+                .cloned()
+                .or_else(|| tablebase.as_ref().and_then(|tb| tb.probe(b)))
+                .unwrap();
+            b.store_score() + score
+        });
+        println!("pie={}", pie);
+    }
+    if let Some(ref path) = config.output {
+        save(path, &board, &db)?;
+    }
+
+    if let Some(depth) = config.compress {
+        let compressed = compress_dag(board, &db, depth);
+        println!("depth={} compressed={}", depth, compressed.len());
+    }
+    Ok(())
+}
+
+fn open_tablebase<B>(path: &str, board: &B) -> Result<Tablebase, String>
+where
+    B: Position,
+{
+    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let tablebase =
+        Tablebase::load(BufReader::new(file)).map_err(|e| format!("{}: {}", path, e))?;
+    if !tablebase.is_for(board) {
+        return Err(format!("{}: 盤面の設定が違います", path));
+    }
+    Ok(tablebase)
+}
+
+/// 穴に残っている石が `--tb-seeds` 個以下の盤面の表を作る
+fn tablebase<P, S>(config: &Config) -> Result<(), String>
+where
+    P: ArrayLength<u8> + Clone + Send,
+    S: Unsigned + Clone + Send,
+{
+    let board = new_board::<P, S>(config);
+    let seeds = config
+        .tb_seeds
+        .ok_or_else(|| "--tb-seeds を指定してください".to_string())?;
+    if index_size(config.pits, seeds).is_none() {
+        return Err("盤面の番号が64bitに収まりません".to_string());
+    }
+    let path = config
+        .output
+        .as_ref()
+        .ok_or_else(|| "--output を指定してください".to_string())?;
+    let tablebase = match config.tablebase {
+        Some(ref base) => {
+            let mut tablebase = open_tablebase(base, &board)?;
+            tablebase.extend(&board, seeds, config.threads);
+            tablebase
+        }
+        None => Tablebase::build(&board, seeds, config.threads),
+    };
+    println!(
+        "{} pits={} tb-seeds={}",
+        tablebase.stealing(),
+        tablebase.pits(),
+        tablebase.seeds()
+    );
+    let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+    tablebase
+        .save(BufWriter::new(file))
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+fn analyze<P, S>(config: &Config) -> Result<(), String>
+where
+    P: ArrayLength<u8> + Clone + Send,
+    S: Unsigned + Clone + Send,
+{
+    let board = new_board::<P, S>(config);
+    let db = score_db(config, &board)?;
+    let mut next_list = board
+        .list_next_with_pos()
+        .drain()
+        .map(|(next, pos)| {
+            let score = -(next.store_score() + lookup(db.as_ref(), &next.key())?);
+            Ok((score, pos))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    next_list.sort();
+    println!("{}", board);
+    for (score, pos) in next_list.iter().rev() {
+        println!("pos={:?} score={}", pos, score);
+    }
+    Ok(())
+}
+
+/// 初期盤面から到達可能な盤面の数
+fn count_positions<P, S>(config: &Config) -> Result<usize, String>
+where
+    P: ArrayLength<u8> + Clone,
+    S: Unsigned + Clone,
+{
+    let mut seen = HashSet::new();
+    let mut stack = vec![new_board::<P, S>(config)];
+    while let Some(board) = stack.pop() {
+        if !seen.insert(board.key()) {
+            continue;
+        }
+        stack.extend(board.list_next());
+    }
+    Ok(seen.len())
+}
+
+fn count<P, S>(config: &Config) -> Result<(), String>
+where
+    P: ArrayLength<u8> + Clone,
+    S: Unsigned + Clone,
+{
+    let count = count_positions::<P, S>(config)?;
+    println!(
+        "{} pits={} seeds={}",
+        config.variant().stealing(),
+        config.pits,
+        config.seeds
+    );
+    println!("count={}", count);
+    Ok(())
+}
+
+/// 初期盤面から先手の石を取り除きながら解き、評価値が 0 に近くなる数を探す
+///
+/// 評価値は手番側から見たもので、負のハンディキャップは相手側から取り除いた数
+fn handicap<P, S>(config: &Config) -> Result<(), String>
+where
+    P: ArrayLength<u8> + Clone + Send,
+    S: Unsigned + Clone + Send,
+{
+    let board = new_board::<P, S>(config);
+    let (pits, seeds, stealing) = board.triple();
+    println!("{} pits={} seeds={}", stealing, pits, seeds);
+    let mut tt = TranspositionTable::new(config.tt_size);
+    let results = search_handicap(&board, |b| {
+        if config.alphabeta {
+            search_mtdf(b, &mut tt, 0)
+        } else {
+            let key = b.key();
+            let db = search_score(b.clone(), config.threads, config.div);
+            b.store_score() + db.get(&key).unwrap()
+        }
+    });
+    for (handicap, score) in results.iter() {
+        println!("handicap={} score={}", handicap, score);
+    }
+    let (fair, _) = results
+        .iter()
+        .min_by_key(|(handicap, score)| (score.abs(), handicap.abs()))
+        .unwrap();
+    println!("fair={}", fair);
+    Ok(())
+}
+
+/// 棋譜の手番ごとに、指した手と最善手の手番側から見た評価値を表示する
+fn review<P, S>(config: &Config, record: &Record) -> Result<(), String>
+where
+    P: ArrayLength<u8> + Clone + Send,
+    S: Unsigned + Clone + Send,
+{
+    let game = record.replay(&new_board::<P, S>(config))?;
+    let db = score_db(config, game.root())?;
+    let value = |b: &GameBoard<P, S>, side: Side| -> Result<i8, String> {
+        let score = b.store_score() + lookup(db.as_ref(), &b.key())?;
+        Ok(if b.side() == side { score } else { -score })
+    };
+    let boards = game.boards();
+    let mut ply = 0;
+    for (i, turn) in record.turns.iter().enumerate() {
+        let before = boards[ply];
+        ply += turn.len();
+        let side = before.side();
+        let (best, best_turn) = before
+            .list_next_with_pos()
+            .into_iter()
+            .map(|(next, pos)| Ok((value(&next, side)?, pos)))
+            .collect::<Result<Vec<_>, String>>()?
+            .into_iter()
+            .max_by_key(|(score, pos)| (*score, Reverse(pos.clone())))
+            .unwrap();
+        // 途中で終わった最後の手番は、続きを最善に蒔いたときの値にする
+        let after = boards[ply];
+        let played = if after.side() == side && !after.is_finished() {
+            after
+                .list_next()
+                .iter()
+                .map(|next| value(next, side))
+                .collect::<Result<Vec<_>, String>>()?
+                .into_iter()
+                .max()
+                .unwrap()
+        } else {
+            value(after, side)?
+        };
+        println!(
+            "{}. {:?} {} value={} best={} ({})",
+            i + 1,
+            side,
+            format_turn(turn),
+            played,
+            best,
+            format_turn(&best_turn)
+        );
+    }
+    if game.board().is_finished() {
+        println!("result={:?}", game.board().scores());
+    }
+    Ok(())
+}
+
+/// `--record` の棋譜を、棋譜ごとに穴の数、石の数、石取りの有無、勝利条件と残りの石の分け方を合わせて `review` する
+///
+/// ルールは `--rules` などで指定したものと棋譜のものが違えばエラーになる
+fn review_records(config: &Config) -> Result<(), String> {
+    let path = config.record.as_ref().unwrap();
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let records = parse_records(&text).map_err(|e| format!("{}: {}", path, e))?;
+    for (i, record) in records.iter().enumerate() {
+        let mut config = config.for_game(record.pits, record.seeds, record.stealing)?;
+        config.win = Some(record.win);
+        config.allocation = record.allocation;
+        println!(
+            "game={} {} pits={} seeds={} first={} second={}",
+            i + 1,
+            record.stealing,
+            record.pits,
+            record.seeds,
+            record.first.as_deref().unwrap_or("?"),
+            record.second.as_deref().unwrap_or("?")
+        );
+        dispatch!(review, &config, record)?;
+    }
+    Ok(())
+}
+
+/// 標準入出力のプロトコルで外部のプログラムから操作する
+///
+/// `newgame` ごとに盤面の大きさが変わるので `DynBoard` を使う
+fn engine(config: &Config) -> Result<(), String> {
+    let base = config.clone();
+    let db_config = config.clone();
+    let mut protocol = Protocol::new(
+        move |pits, seeds, stealing| {
+            let config = base.for_game(pits, seeds, stealing)?;
+            Ok(DynBoard::with_rules(pits, seeds, config.variant())
+                .with_win_condition(config.win())
+                .with_allocation(config.allocation))
+        },
+        move |board: &DynBoard<Variant>| -> Result<ScoreDb<DynBoard<Variant>>, String> {
+            match db_config.db {
+                Some(ref path) => open(path, board),
+                None => {
+                    let db: HashMap<_, _> =
+                        search_score(board.clone(), db_config.threads, db_config.div).into();
+                    Ok(Box::new(db))
+                }
+            }
+        },
+    );
+    let stdin = stdin();
+    protocol
+        .run(stdin.lock(), stdout())
+        .map_err(|e| e.to_string())
+}
+
+/// 初期盤面の評価値。`--pie` ならパイルールでの値
+fn root_score<P, S>(config: &Config) -> Result<i8, String>
+where
+    P: ArrayLength<u8> + Clone + Send,
+    S: Unsigned + Clone + Send,
+{
+    let board = new_board::<P, S>(config);
+    let key = board.key();
+    let db = search_score(board.clone(), config.threads, config.div);
+    if config.pie {
+        return Ok(pie_score(&board, |b| {
+            b.store_score() + db.get(&b.key()).unwrap()
+        }));
+    }
+    Ok(db.get(&key).unwrap())
+}
+
+/// README と同じ形式の表を出力する
+fn table(config: &Config) -> Result<(), String> {
+    print!("| 穴の数\\石の数 |");
+    for seeds in 1..=config.seeds {
+        print!(" {} |", seeds);
+    }
+    println!();
+    print!("|--------------:|");
+    for _ in 1..=config.seeds {
+        print!("--:|");
+    }
+    println!();
+    for pits in 1..=config.pits {
+        print!("| {:>13} |", pits);
+        for seeds in 1..=config.seeds {
+            let cell = Config {
+                pits,
+                seeds,
+                ..config.clone()
+            };
+            if 2 * pits * seeds > i8::MAX as usize || index_size(pits, 2 * pits * seeds).is_none() {
+                print!("   |");
+                continue;
+            }
+            let score = dispatch!(root_score, &cell)?;
+            print!(" {} |", score);
+        }
+        println!();
+    }
+    Ok(())
+}
+
+fn run(config: &Config) -> Result<(), String> {
+    match config.command {
+        Command::Solve => dispatch!(solve, config),
+        Command::Score => dispatch!(score, config),
+        Command::Play => dispatch!(play, config),
+        Command::Analyze => dispatch!(analyze, config),
+        Command::Count => dispatch!(count, config),
+        Command::Table => table(config),
+        Command::Tablebase => dispatch!(tablebase, config),
+        Command::Handicap => dispatch!(handicap, config),
+        Command::Review => review_records(config),
+        Command::Engine => engine(config),
+    }
+}
+
+fn main() {
+    let result = Config::parse(std::env::args().skip(1)).and_then(|config| run(&config));
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
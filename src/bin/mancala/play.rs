@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{stdin, stdout, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use generic_array::ArrayLength;
+use mancala_full_search::{
+    settlement_key, DbLookup, Engine, Game, Position, Record, Renderer, Settlement, Side,
+};
+use typenum::Unsigned;
+
+use crate::args::Config;
+use crate::{clean_db, lookup, new_board, GameBoard};
+
+/// 入力された操作
+enum Input {
+    Sow(usize),
+    Undo,
+    Redo,
+    /// 入力が終わった
+    Quit,
+}
+
+fn get_input<P, S>(renderer: &Renderer, board: &GameBoard<P, S>) -> Input
+where
+    P: ArrayLength<u8> + Clone,
+    S: Unsigned + Clone,
+{
+    println!("{}", renderer.render(board));
+    loop {
+        print!("Side {:?} (u: 戻す, r: 進める): ", board.side);
+        stdout().flush().unwrap();
+        let mut buf = String::new();
+        if stdin().read_line(&mut buf).unwrap() == 0 {
+            return Input::Quit;
+        }
+        match buf.trim() {
+            "u" => return Input::Undo,
+            "r" => return Input::Redo,
+            s => match s.parse() {
+                Ok(i) => match board.can_sow(i) {
+                    Ok(_) => {
+                        return Input::Sow(i);
+                    }
+                    Err(e) => eprintln!("{}", e),
+                },
+                Err(e) => eprintln!("{}", e),
+            },
+        }
+    }
+}
+
+fn print_suggest<P, S>(
+    renderer: &Renderer,
+    db: &dyn DbLookup<u128, Settlement>,
+    board: &GameBoard<P, S>,
+) -> Result<(), String>
+where
+    P: ArrayLength<u8> + Clone,
+    S: Unsigned + Clone,
+{
+    let mut next_list = board
+        .list_next_with_pos()
+        .drain()
+        .map(|(b, v)| Ok((lookup(db, &settlement_key(&b))?, b, v)))
+        .collect::<Result<Vec<_>, String>>()?;
+    next_list.sort_by_key(|(score, _, _)| *score);
+    // 候補の盤面は添字を省いて短くする
+    let renderer = Renderer {
+        indices: false,
+        ..*renderer
+    };
+    println!("#########################");
+    for (score, b, v) in next_list.iter().take(3) {
+        println!("{}", renderer.render(b));
+        println!("pos={:?}", v);
+        println!("score={:?}", -*score);
+        println!();
+    }
+    println!("------------------------");
+    Ok(())
+}
+
+pub fn play<P, S>(config: &Config) -> Result<(), String>
+where
+    P: ArrayLength<u8> + Clone + Send,
+    S: Unsigned + Clone + Send,
+{
+    let origin_board = new_board::<P, S>(config);
+    let board = origin_board.clone();
+    let key = settlement_key(&board);
+    let (pits, seeds, stealing) = board.triple();
+    let db = clean_db(config, &board)?;
+    println!("{} pits={} seeds={}", stealing, pits, seeds);
+    println!("score={}", lookup(db.as_ref(), &key)?);
+    let renderer = Renderer {
+        color: config.color,
+        ..Renderer::default()
+    };
+    let seed = config.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+    });
+    let mut engine = Engine::new(config.level, seed);
+    // コンピュータが指す側
+    let computer = config.side.map(Side::turned);
+
+    loop {
+        println!("******************");
+        println!("start battle");
+        println!("******************");
+        let mut game = Game::new(origin_board.clone());
+        while !game.board().is_finished() {
+            if Some(game.board().side()) == computer {
+                let values = game
+                    .board()
+                    .list_next()
+                    .into_iter()
+                    .map(|next| {
+                        let key = settlement_key(&next);
+                        Ok((key, lookup(db.as_ref(), &key)?))
+                    })
+                    .collect::<Result<HashMap<_, _>, String>>()?;
+                let turn = engine
+                    .choose(game.board(), |next| -values[&settlement_key(next)])
+                    .unwrap();
+                println!("{}", renderer.render(game.board()));
+                println!("computer: pos={:?}", turn);
+                for pos in turn {
+                    game.play(pos)?;
+                }
+                continue;
+            }
+            // コンピュータと対局するときは探索結果を見せない
+            if computer.is_none() {
+                print_suggest(&renderer, db.as_ref(), game.board())?;
+            }
+            match get_input(&renderer, game.board()) {
+                Input::Sow(pos) => game.play(pos)?,
+                Input::Undo => {
+                    if !game.undo() {
+                        eprintln!("これ以上戻せません");
+                    }
+                    // コンピュータの手番は飛ばして人の手番まで戻す
+                    while computer.is_some() && Some(game.board().side()) == computer {
+                        if !game.undo() {
+                            break;
+                        }
+                    }
+                }
+                Input::Redo => {
+                    if !game.redo() {
+                        eprintln!("これ以上進められません");
+                    }
+                    while computer.is_some() && Some(game.board().side()) == computer {
+                        if !game.redo() {
+                            break;
+                        }
+                    }
+                }
+                Input::Quit => {
+                    // 途中の対局も残しておく
+                    if let (Some(path), true) = (&config.record, game.ply() > 0) {
+                        append_record(path, &record(config, &game))?;
+                    }
+                    return Ok(());
+                }
+            }
+        }
+        println!("{}", renderer.render(game.board()));
+        println!("result={:?}", game.board().scores());
+        if let Some(ref path) = config.record {
+            append_record(path, &record(config, &game))?;
+        }
+    }
+}
+
+/// コンピュータと対局したときは対局者を書いた棋譜
+fn record<P, S>(config: &Config, game: &Game<GameBoard<P, S>>) -> Record
+where
+    P: ArrayLength<u8> + Clone,
+    S: Unsigned + Clone,
+{
+    let mut record = Record::from_game(game);
+    let name = |side| {
+        if config.side == Some(side) {
+            "human".to_string()
+        } else {
+            format!("computer level={}", config.level.mistake)
+        }
+    };
+    if config.side.is_some() {
+        record.first = Some(name(Side::First));
+        record.second = Some(name(Side::Second));
+    }
+    record
+}
+
+fn append_record(path: &str, record: &Record) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    writeln!(file, "{}", record).map_err(|e| format!("{}: {}", path, e))
+}
@@ -1,26 +1,31 @@
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
+use std::str::FromStr;
 
 use generic_array::{ArrayLength, GenericArray};
-use typenum::{
-    consts::{U1, U2, U3, U4, U5, U6, U7, U8},
-    Unsigned,
-};
+use typenum::Unsigned;
+
+use crate::rank::{index_size, rank, MAX_PITS};
+use crate::rules::sow_seeds;
+use crate::{Kalah, Notation, Renderer, Ruleset};
 
 #[derive(Debug, Clone)]
-pub struct Board<P, S>
+pub struct Board<P, S, R = Kalah>
 where
     P: ArrayLength<u8>,
 {
-    stealing: bool,
+    rules: R,
+    win: WinCondition,
+    allocation: Allocation,
     pub side: Side,
     pits: [GenericArray<u8, P>; 2],
     stores: [u8; 2],
     _game: PhantomData<S>,
 }
 
-impl<P, S> Hash for Board<P, S>
+impl<P, S, R> Hash for Board<P, S, R>
 where
     P: ArrayLength<u8>,
     S: Unsigned,
@@ -33,7 +38,7 @@ where
     }
 }
 
-impl<P, S> PartialEq for Board<P, S>
+impl<P, S, R> PartialEq for Board<P, S, R>
 where
     P: ArrayLength<u8>,
     S: Unsigned,
@@ -43,13 +48,24 @@ where
     }
 }
 
-impl<P, S> Eq for Board<P, S>
+impl<P, S, R> Eq for Board<P, S, R>
 where
     P: ArrayLength<u8>,
     S: Unsigned,
 {
 }
 
+impl<P, S, R> fmt::Display for Board<P, S, R>
+where
+    P: ArrayLength<u8> + Clone,
+    S: Unsigned + Clone,
+    R: Ruleset,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&Renderer::default().render(self))
+    }
+}
+
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
 pub enum Side {
     First,
@@ -58,6 +74,97 @@ pub enum Side {
 
 use Side::*;
 
+/// パイルールで、盤面はそのままにして相手と席を入れ替える手
+///
+/// `Position::list_next_with_swap` だけが返し、`sow` や `can_sow` には渡せない
+pub const SWAP: usize = usize::MAX;
+
+/// 勝敗の決め方
+///
+/// どれもどちらかの側の穴の石がなくなったら終了する
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum WinCondition {
+    /// 最終スコアが大きいほうが勝ち (スコア性)
+    Score,
+    /// 先に自分の側の石を全てなくしたほうが勝ち (早なくし)
+    ///
+    /// 正確には終了させる手を指したほうが勝ちで、石を取って相手の側をなくした場合も指した側の勝ちにする
+    Empty,
+    /// 最終スコアが小さいほうが勝ち
+    Misere,
+}
+
+impl FromStr for WinCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "score" => Ok(WinCondition::Score),
+            "empty" => Ok(WinCondition::Empty),
+            "misere" => Ok(WinCondition::Misere),
+            _ => Err(format!("不明な勝利条件です: {}", s)),
+        }
+    }
+}
+
+/// `FromStr` で読める名前
+impl fmt::Display for WinCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            WinCondition::Score => "score",
+            WinCondition::Empty => "empty",
+            WinCondition::Misere => "misere",
+        })
+    }
+}
+
+/// 終了時に穴に残っている石を誰のスコアにするか
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Allocation {
+    /// それぞれの側の石をその側のものにする
+    Owner,
+    /// 全て先に自分の側の石をなくした側のものにする
+    Emptied,
+    /// 全て先に自分の側の石をなくした側の相手のものにする
+    Opponent,
+}
+
+impl FromStr for Allocation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "owner" => Ok(Allocation::Owner),
+            "emptied" => Ok(Allocation::Emptied),
+            "opponent" => Ok(Allocation::Opponent),
+            _ => Err(format!("不明な残りの石の分け方です: {}", s)),
+        }
+    }
+}
+
+/// `FromStr` で読める名前
+impl fmt::Display for Allocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Allocation::Owner => "owner",
+            Allocation::Emptied => "emptied",
+            Allocation::Opponent => "opponent",
+        })
+    }
+}
+
+impl FromStr for Side {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(First),
+            "second" => Ok(Second),
+            _ => Err(format!("不明な手番です: {}", s)),
+        }
+    }
+}
+
 impl Side {
     #[inline]
     pub fn as_usize(self) -> usize {
@@ -76,120 +183,192 @@ impl Side {
     }
 }
 
-impl<P, S> Board<P, S>
-where
-    P: ArrayLength<u8> + Clone,
-    S: Unsigned + Clone,
-{
-    pub fn new(stealing: bool) -> Board<P, S> {
-        let pits = vec![S::to_u8(); P::to_usize()];
-        Board {
-            stealing,
-            side: First,
-            pits: [
-                GenericArray::clone_from_slice(&pits),
-                GenericArray::clone_from_slice(&pits),
-            ],
-            stores: [0, 0],
-            _game: PhantomData,
+/// 盤面の大きさを型で持つか値で持つかによらない共通の操作
+pub trait Position: Clone + Hash + Eq {
+    type Rules: Ruleset;
+
+    /// (穴の数, 初期の石の数, 石取りの有無)
+    fn triple(&self) -> (usize, usize, bool);
+
+    fn rules(&self) -> &Self::Rules;
+
+    fn win_condition(&self) -> WinCondition;
+
+    fn allocation(&self) -> Allocation;
+
+    fn side(&self) -> Side;
+
+    fn pits(&self, side: Side) -> &[u8];
+
+    fn stores(&self) -> [u8; 2];
+
+    fn sow(&mut self, pos: usize);
+
+    /// 設定はそのままで、手番を先手、先手の穴を `self_pits`、後手の穴を `opposite_pits` にし、
+    /// ストアを空にした盤面を作る
+    fn with_pits(&self, self_pits: &[u8], opposite_pits: &[u8]) -> Self;
+
+    /// 設定と穴はそのままで、ストアと手番を変えた盤面を作る
+    fn with_stores(&self, stores: [u8; 2], side: Side) -> Self;
+
+    /// 穴、ストア、手番を表記にする
+    fn notation(&self) -> Notation {
+        Notation {
+            pits: [self.pits(First).to_vec(), self.pits(Second).to_vec()],
+            stores: self.stores(),
+            side: self.side(),
         }
     }
 
-    pub fn triple(&self) -> (usize, usize, bool) {
-        (P::to_usize(), S::to_usize(), self.stealing)
+    /// 設定はそのままで、穴、ストア、手番を `notation` にした盤面を作る
+    ///
+    /// 穴の数が違うか、石が初期配置の合計より多ければエラー
+    fn with_notation(&self, notation: &Notation) -> Result<Self, String> {
+        let (pits, seeds, _) = self.triple();
+        if notation.pits[0].len() != pits {
+            return Err(format!("穴の数を{}にしてください", pits));
+        }
+        if notation.seeds() > 2 * pits * seeds {
+            return Err(format!("石の合計を{}以下にしてください", 2 * pits * seeds));
+        }
+        Ok(self
+            .with_pits(&notation.pits[0], &notation.pits[1])
+            .with_stores(notation.stores, notation.side))
     }
 
-    /// 終了判定
-    pub fn is_finished(&self) -> bool {
-        self.pits[0].iter().all(|s| *s == 0) || self.pits[1].iter().all(|s| *s == 0)
+    fn self_pits(&self) -> &[u8] {
+        self.pits(self.side())
     }
 
-    pub fn scores(&self) -> (u8, u8) {
-        let s0 = self.stores[0] + self.pits[0].iter().sum::<u8>();
-        let s1 = self.stores[1] + self.pits[1].iter().sum::<u8>();
-        (s0, s1)
+    fn opposite_pits(&self) -> &[u8] {
+        self.pits(self.side().turned())
     }
 
-    pub fn score(&self) -> i8 {
-        let (s0, s1) = self.scores();
-        if self.side == First {
-            s0 as i8 - s1 as i8
+    /// 終了判定
+    ///
+    /// 相手に石を渡さなければならないルールでは、手番側が蒔ける穴がなくなったら終了
+    fn is_finished(&self) -> bool {
+        if self.rules().must_feed() {
+            self.movable().is_empty()
         } else {
-            s1 as i8 - s0 as i8
+            self.pits(First).iter().all(|s| *s == 0) || self.pits(Second).iter().all(|s| *s == 0)
         }
     }
 
-    pub fn store_score(&self) -> i8 {
-        if self.side == First {
-            self.stores[0] as i8 - self.stores[1] as i8
-        } else {
-            self.stores[1] as i8 - self.stores[0] as i8
+    /// 今の手番で蒔ける穴
+    ///
+    /// 相手に石を渡さなければならないルールで相手の穴が空なら、相手の側に石が入る手だけを返す
+    fn movable(&self) -> Vec<usize> {
+        let mut list = self
+            .self_pits()
+            .iter()
+            .enumerate()
+            .filter(|(_, &s)| s > 0)
+            .map(|(pos, _)| pos)
+            .collect::<Vec<_>>();
+        if self.rules().must_feed() && self.opposite_pits().iter().all(|s| *s == 0) {
+            let opposite = self.side().turned();
+            list.retain(|&pos| {
+                let mut board = self.clone();
+                board.sow(pos);
+                board.pits(opposite).iter().any(|s| *s > 0)
+            });
         }
+        list
     }
 
-    pub fn pit_score(&self) -> i8 {
-        let s0 = self.pits[0].iter().sum::<u8>();
-        let s1 = self.pits[1].iter().sum::<u8>();
-        if self.side == First {
-            s0 as i8 - s1 as i8
-        } else {
-            s1 as i8 - s0 as i8
-        }
+    /// 穴に残っている石を `allocated` で分けたスコア
+    fn scores(&self) -> (u8, u8) {
+        let stores = self.stores();
+        let rest = self.allocated();
+        (stores[0] + rest[0], stores[1] + rest[1])
     }
 
-    pub fn self_pits(&self) -> &GenericArray<u8, P> {
-        &self.pits[self.side.as_usize()]
+    /// 穴に残っている石のうち、それぞれのスコアに加える数
+    ///
+    /// `Ruleset::allocate` で分けた後、終了した盤面なら `allocation` に従って移す。
+    /// 終了していない盤面ではそれぞれの側のものとして扱う
+    fn allocated(&self) -> [u8; 2] {
+        let remaining = self.rules().allocate([
+            self.pits(First).iter().sum::<u8>(),
+            self.pits(Second).iter().sum::<u8>(),
+        ]);
+        let emptied = [First, Second]
+            .iter()
+            .copied()
+            .find(|s| self.pits(*s).iter().all(|n| *n == 0));
+        let receiver = match self.allocation() {
+            Allocation::Owner => return remaining,
+            Allocation::Emptied => emptied,
+            Allocation::Opponent => emptied.map(Side::turned),
+        };
+        match receiver {
+            Some(receiver) if self.is_finished() => {
+                let mut allocated = [0, 0];
+                allocated[receiver.as_usize()] = remaining[0] + remaining[1];
+                allocated
+            }
+            _ => remaining,
+        }
     }
 
-    pub fn opposite_pits(&self) -> &GenericArray<u8, P> {
-        &self.pits[self.side.turned().as_usize()]
+    /// 手番側から見た評価値。お互いこれを大きくしようとする
+    ///
+    /// スコア性ではスコア差、ミゼールではその符号を逆にしたもの、
+    /// 早なくしでは終了した盤面で勝ちなら 1、負けなら -1 になる。
+    /// 常に `store_score() + pit_score()` に等しい
+    fn score(&self) -> i8 {
+        self.store_score() + self.pit_score()
     }
 
-    fn move_seed(&mut self, side: Side, pos: usize, num: usize) -> (Side, usize) {
-        if pos + num <= P::to_usize() {
-            for i in pos..pos + num {
-                self.pits[side.as_usize()][i] += 1;
-            }
-            return (side, pos + num - 1);
-        }
-        for i in pos..P::to_usize() {
-            self.pits[side.as_usize()][i] += 1;
+    /// 評価値のうちストアの石で決まっている分
+    fn store_score(&self) -> i8 {
+        let stores = self.stores();
+        let diff = if self.side() == First {
+            stores[0] as i8 - stores[1] as i8
+        } else {
+            stores[1] as i8 - stores[0] as i8
+        };
+        match self.win_condition() {
+            WinCondition::Score => diff,
+            WinCondition::Misere => -diff,
+            WinCondition::Empty => 0,
         }
-        if self.side == side {
-            self.stores[side.as_usize()] += 1;
-            if pos + num == P::to_usize() + 1 {
-                return (side, P::to_usize());
-            }
-            self.move_seed(side.turned(), 0, pos + num - P::to_usize() - 1)
+    }
+
+    /// 評価値のうち穴に残っている石で決まる分。早なくしでは終了していない盤面で 0
+    fn pit_score(&self) -> i8 {
+        let [s0, s1] = self.allocated();
+        let diff = if self.side() == First {
+            s0 as i8 - s1 as i8
         } else {
-            self.move_seed(side.turned(), 0, pos + num - P::to_usize())
+            s1 as i8 - s0 as i8
+        };
+        match self.win_condition() {
+            WinCondition::Score => diff,
+            WinCondition::Misere => -diff,
+            // 終了した盤面の手番側は終了させた手を指していないので負け
+            WinCondition::Empty if self.is_finished() => -1,
+            WinCondition::Empty => 0,
         }
     }
 
-    pub fn sow(&mut self, pos: usize) {
-        let num = self.pits[self.side.as_usize()][pos];
-        self.pits[self.side.as_usize()][pos] = 0;
-        let (side, end_pos) = self.move_seed(self.side, pos + 1, num as usize);
-        if side == self.side {
-            if end_pos == P::to_usize() {
-                if !self.is_finished() {
-                    return;
-                }
-            } else if self.stealing && self.pits[side.as_usize()][end_pos] == 1 {
-                let opposite_pos = P::to_usize() - 1 - end_pos;
-                let opposite_num = self.pits[side.turned().as_usize()][opposite_pos];
-                if opposite_num > 0 {
-                    self.pits[side.as_usize()][end_pos] = 0;
-                    self.pits[side.turned().as_usize()][opposite_pos] = 0;
-                    self.stores[side.as_usize()] += opposite_num + 1;
-                }
-            }
+    fn can_sow(&self, pos: usize) -> Result<(), String> {
+        let pits = self.self_pits();
+        if pos >= pits.len() {
+            return Err(format!("0から{}の間で指定してください", pits.len() - 1));
+        }
+        if pits[pos] == 0 {
+            return Err("そこには石が残っていません".to_string());
+        }
+        if !self.movable().contains(&pos) {
+            return Err("相手に石を渡す手を選んでください".to_string());
         }
-        self.side = self.side.turned();
+        Ok(())
     }
 
     /// 今の手番での手を全列挙してそれを行った場合のユニークな盤面のセットを返す
-    pub fn list_next(&self) -> HashSet<Board<P, S>> {
+    fn list_next(&self) -> HashSet<Self> {
         let mut set = HashSet::with_capacity(7 * 4);
         if self.is_finished() {
             return set;
@@ -197,13 +376,10 @@ where
         let mut stack = Vec::with_capacity(4);
         stack.push(self.clone());
         while let Some(board) = stack.pop() {
-            for (pos, &s) in board.self_pits().iter().enumerate() {
-                if s == 0 {
-                    continue;
-                }
+            for pos in board.movable() {
                 let mut copied = board.clone();
                 copied.sow(pos);
-                if copied.side == self.side {
+                if copied.side() == self.side() {
                     stack.push(copied);
                 } else {
                     set.insert(copied);
@@ -213,22 +389,9 @@ where
         set
     }
 
-    pub fn can_sow(&self, pos: usize) -> Result<(), String> {
-        if pos >= P::to_usize() {
-            return Err(format!(
-                "0から{}の間で指定してください",
-                P::to_usize() - 1
-            ));
-        }
-        if self.pits[self.side.as_usize()][pos] == 0 {
-            return Err("そこには石が残っていません".to_string());
-        }
-        Ok(())
-    }
-
     /// 次のターンの盤面とその盤面にするために必要な打ち手のペアの一覧を返す
     /// `std::collections::HashMap` を返すので、返り値を `iter` した順序は毎回異なることを期待して良い
-    pub fn list_next_with_pos(&self) -> HashMap<Board<P, S>, Vec<usize>> {
+    fn list_next_with_pos(&self) -> HashMap<Self, Vec<usize>> {
         let mut map = HashMap::with_capacity(7 * 4);
         if self.is_finished() {
             return map;
@@ -236,15 +399,12 @@ where
         let mut stack = Vec::with_capacity(4);
         stack.push((self.clone(), Vec::with_capacity(1)));
         while let Some((board, pos_list)) = stack.pop() {
-            for (pos, &s) in board.self_pits().iter().enumerate() {
-                if s == 0 {
-                    continue;
-                }
+            for pos in board.movable() {
                 let mut copied = board.clone();
                 let mut copied_pos = pos_list.clone();
                 copied.sow(pos);
                 copied_pos.push(pos);
-                if copied.side == self.side {
+                if copied.side() == self.side() {
                     stack.push((copied, copied_pos));
                 } else {
                     map.entry(copied).or_insert(copied_pos);
@@ -253,183 +413,172 @@ where
         }
         map
     }
-}
 
-pub trait CompactKey {
-    type Key: Hash + Eq;
-    fn key(&self) -> Self::Key;
-}
-
-impl<S: Unsigned + Clone> CompactKey for Board<U1, S> {
-    type Key = u16;
-    fn key(&self) -> Self::Key {
-        (u16::from(self.self_pits()[0]) << 8) + u16::from(self.opposite_pits()[0])
+    /// パイルールでの後手の最初の手番。`list_next_with_pos` に席を入れ替える手 `SWAP` を加えたもの
+    ///
+    /// 入れ替えると相手がこの盤面の手番を持つので、その手の次の盤面はこの盤面自身になる。
+    /// 終了した盤面では入れ替えられない
+    ///
+    /// `SWAP` を含む手の一覧はこれが唯一の入口で、`list_next` や `list_next_with_pos` には
+    /// パイルールの有無によらず `SWAP` は入らない。パイルールでの値は `pie_score` がこれを使って求める
+    fn list_next_with_swap(&self) -> HashMap<Self, Vec<usize>> {
+        let mut map = self.list_next_with_pos();
+        if !self.is_finished() {
+            map.insert(self.clone(), vec![SWAP]);
+        }
+        map
     }
 }
 
-impl<S: Unsigned + Clone> CompactKey for Board<U2, S> {
-    type Key = u32;
-    fn key(&self) -> Self::Key {
-        let k0 = {
-            let p = self.self_pits();
-            (u32::from(p[0]) << 8) + u32::from(p[1])
-        };
-        let k1 = {
-            let p = self.opposite_pits();
-            (u32::from(p[0]) << 8) + u32::from(p[1])
-        };
-        (k0 << 16) + k1
+impl<P, S> Board<P, S>
+where
+    P: ArrayLength<u8> + Clone,
+    S: Unsigned + Clone,
+{
+    /// カラハのルールで、勝利条件はスコア性
+    pub fn new(stealing: bool) -> Board<P, S> {
+        Board::with_rules(Kalah::new(stealing))
     }
 }
 
-impl<S: Unsigned + Clone> CompactKey for Board<U3, S> {
-    type Key = u64;
-    fn key(&self) -> Self::Key {
-        macro_rules! shift {
-            ($p:ident, $t:ty) => {
-                (<$t>::from($p[0]) << 16) + (<$t>::from($p[1]) << 8) + <$t>::from($p[2])
-            };
+impl<P, S, R> Board<P, S, R>
+where
+    P: ArrayLength<u8> + Clone,
+    S: Unsigned + Clone,
+    R: Ruleset,
+{
+    /// 勝利条件はスコア性
+    pub fn with_rules(rules: R) -> Board<P, S, R> {
+        let (pits, seeds) = (P::to_usize(), S::to_usize());
+        assert!(pits > 0, "穴の数は1以上にしてください");
+        assert!(
+            2 * pits * seeds <= usize::from(u8::MAX),
+            "石の総数が多すぎます"
+        );
+        assert!(pits <= MAX_PITS, "穴の数は{}以下にしてください", MAX_PITS);
+        assert!(
+            index_size(pits, 2 * pits * seeds).is_some(),
+            "キーが64bitに収まりません"
+        );
+        let pits = vec![S::to_u8(); pits];
+        Board {
+            rules,
+            win: WinCondition::Score,
+            allocation: Allocation::Owner,
+            side: First,
+            pits: [
+                GenericArray::clone_from_slice(&pits),
+                GenericArray::clone_from_slice(&pits),
+            ],
+            stores: [0, 0],
+            _game: PhantomData,
         }
-        let k0 = {
-            let p = self.self_pits();
-            shift!(p, u32)
-        };
-        let k1 = {
-            let p = self.opposite_pits();
-            shift!(p, u32)
-        };
-        (u64::from(k0) << 32) + u64::from(k1)
     }
-}
 
-impl<S: Unsigned + Clone> CompactKey for Board<U4, S> {
-    type Key = u64;
-    fn key(&self) -> Self::Key {
-        macro_rules! shift {
-            ($p:ident, $t:ty) => {
-                (<$t>::from($p[0]) << 24)
-                    + (<$t>::from($p[1]) << 16)
-                    + (<$t>::from($p[2]) << 8)
-                    + <$t>::from($p[3])
-            };
-        }
-        let k0 = {
-            let p = self.self_pits();
-            shift!(p, u32)
-        };
-        let k1 = {
-            let p = self.opposite_pits();
-            shift!(p, u32)
-        };
-        (u64::from(k0) << 32) + u64::from(k1)
+    pub fn with_win_condition(mut self, win: WinCondition) -> Board<P, S, R> {
+        self.win = win;
+        self
     }
-}
 
-impl<S: Unsigned + Clone> CompactKey for Board<U5, S> {
-    type Key = u64;
-    fn key(&self) -> Self::Key {
-        macro_rules! shift {
-            ($p:ident, $t:ty) => {
-                (<$t>::from($p[0]) << 24)
-                    + (<$t>::from($p[1]) << 18)
-                    + (<$t>::from($p[2]) << 12)
-                    + (<$t>::from($p[3]) << 6)
-                    + <$t>::from($p[4])
-            };
-        }
-        let k0 = {
-            let p = self.self_pits();
-            shift!(p, u32)
-        };
-        let k1 = {
-            let p = self.opposite_pits();
-            shift!(p, u32)
-        };
-        (u64::from(k0) << 32) + u64::from(k1)
+    pub fn with_allocation(mut self, allocation: Allocation) -> Board<P, S, R> {
+        self.allocation = allocation;
+        self
     }
 }
 
-impl<S: Unsigned + Clone> CompactKey for Board<U6, S> {
-    type Key = u64;
-    fn key(&self) -> Self::Key {
-        macro_rules! shift {
-            ($p:ident, $t:ty) => {
-                (<$t>::from($p[0]) << 25)
-                    + (<$t>::from($p[1]) << 20)
-                    + (<$t>::from($p[2]) << 15)
-                    + (<$t>::from($p[3]) << 10)
-                    + (<$t>::from($p[4]) << 5)
-                    + <$t>::from($p[5])
-            };
+impl<P, S, R> Position for Board<P, S, R>
+where
+    P: ArrayLength<u8> + Clone,
+    S: Unsigned + Clone,
+    R: Ruleset,
+{
+    type Rules = R;
+
+    fn triple(&self) -> (usize, usize, bool) {
+        (P::to_usize(), S::to_usize(), self.rules.stealing())
+    }
+
+    fn rules(&self) -> &R {
+        &self.rules
+    }
+
+    fn win_condition(&self) -> WinCondition {
+        self.win
+    }
+
+    fn allocation(&self) -> Allocation {
+        self.allocation
+    }
+
+    fn side(&self) -> Side {
+        self.side
+    }
+
+    fn pits(&self, side: Side) -> &[u8] {
+        &self.pits[side.as_usize()]
+    }
+
+    fn stores(&self) -> [u8; 2] {
+        self.stores
+    }
+
+    fn with_pits(&self, self_pits: &[u8], opposite_pits: &[u8]) -> Self {
+        Board {
+            rules: self.rules.clone(),
+            win: self.win,
+            allocation: self.allocation,
+            side: First,
+            pits: [
+                GenericArray::clone_from_slice(self_pits),
+                GenericArray::clone_from_slice(opposite_pits),
+            ],
+            stores: [0, 0],
+            _game: PhantomData,
         }
-        let k0 = {
-            let p = self.self_pits();
-            shift!(p, u32)
-        };
-        let k1 = {
-            let p = self.opposite_pits();
-            shift!(p, u32)
-        };
-        (u64::from(k0) << 32) + u64::from(k1)
     }
-}
 
-impl<S: Unsigned + Clone> CompactKey for Board<U7, S> {
-    type Key = u64;
-    fn key(&self) -> Self::Key {
-        macro_rules! shift {
-            ($p:ident, $t:ty) => {
-                (<$t>::from($p[0]) << 24)
-                    + (<$t>::from($p[1]) << 20)
-                    + (<$t>::from($p[2]) << 16)
-                    + (<$t>::from($p[3]) << 12)
-                    + (<$t>::from($p[4]) << 8)
-                    + (<$t>::from($p[5]) << 4)
-                    + <$t>::from($p[6])
-            };
+    fn with_stores(&self, stores: [u8; 2], side: Side) -> Self {
+        Board {
+            stores,
+            side,
+            ..self.clone()
         }
-        let k0 = {
-            let p = self.self_pits();
-            shift!(p, u32)
-        };
-        let k1 = {
-            let p = self.opposite_pits();
-            shift!(p, u32)
-        };
-        (u64::from(k0) << 32) + u64::from(k1)
+    }
+
+    fn sow(&mut self, pos: usize) {
+        let [first, second] = &mut self.pits;
+        self.side = sow_seeds(
+            &self.rules,
+            [first, second],
+            &mut self.stores,
+            self.side,
+            pos,
+        );
     }
 }
 
-impl<S: Unsigned + Clone> CompactKey for Board<U8, S> {
+/// 手番側から見た石の配置だけで盤面を区別するキー
+pub trait CompactKey {
+    type Key: Hash + Eq + Clone;
+    fn key(&self) -> Self::Key;
+}
+
+impl<P, S, R> CompactKey for Board<P, S, R>
+where
+    P: ArrayLength<u8> + Clone,
+    S: Unsigned + Clone,
+    R: Ruleset,
+{
     type Key = u64;
     fn key(&self) -> Self::Key {
-        macro_rules! shift {
-            ($p:ident, $t:ty) => {
-                (<$t>::from($p[0]) << 28)
-                    + (<$t>::from($p[1]) << 24)
-                    + (<$t>::from($p[2]) << 20)
-                    + (<$t>::from($p[3]) << 16)
-                    + (<$t>::from($p[4]) << 12)
-                    + (<$t>::from($p[5]) << 8)
-                    + (<$t>::from($p[6]) << 4)
-                    + <$t>::from($p[7])
-            };
-        }
-        let k0 = {
-            let p = self.self_pits();
-            shift!(p, u32)
-        };
-        let k1 = {
-            let p = self.opposite_pits();
-            shift!(p, u32)
-        };
-        (u64::from(k0) << 32) + u64::from(k1)
+        rank(self.self_pits().iter().chain(self.opposite_pits()).cloned())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use typenum::{U1, U2, U3, U7, U8};
 
     #[test]
     fn smoke_1_2() {
@@ -459,4 +608,92 @@ mod tests {
         let key2 = board.key();
         assert_ne!(key1, key2);
     }
+
+    #[test]
+    fn key_large_pit() {
+        // ビットシフトで詰めていた頃は 16 << 20 と 1 << 24 が衝突していた
+        let mut a = Board::<U7, U8>::new(true);
+        let mut b = Board::<U7, U8>::new(true);
+        a.pits[0] = GenericArray::clone_from_slice(&[0, 16, 0, 0, 0, 0, 0]);
+        b.pits[0] = GenericArray::clone_from_slice(&[1, 0, 0, 0, 0, 0, 0]);
+        assert_ne!(a.key(), b.key());
+    }
+
+    #[test]
+    #[should_panic(expected = "キーが64bitに収まりません")]
+    fn key_overflow() {
+        Board::<U8, U7>::new(true);
+    }
+
+    #[test]
+    fn win_condition() {
+        assert_eq!("misere".parse(), Ok(WinCondition::Misere));
+        assert!("first".parse::<WinCondition>().is_err());
+
+        let mut board = Board::<U1, U2>::new(true);
+        board.sow(0);
+        assert_eq!(board.scores(), (1, 3));
+        assert_eq!(board.score(), 2);
+        let board = board.with_win_condition(WinCondition::Misere);
+        assert_eq!(board.score(), -2);
+        // 先手が先に石をなくしたので、手番の後手の負け
+        let board = board.with_win_condition(WinCondition::Empty);
+        assert_eq!((board.store_score(), board.pit_score()), (0, -1));
+
+        // 先手が石を取って後手の側をなくして終了させても、手番の後手の負け
+        let mut board = Board::<U3, U1>::new(true)
+            .with_win_condition(WinCondition::Empty)
+            .with_pits(&[1, 0, 1], &[0, 1, 0]);
+        board.sow(0);
+        assert!(board.is_finished());
+        assert_eq!(board.side(), Second);
+        assert_eq!(board.score(), -1);
+    }
+
+    #[test]
+    fn allocation() {
+        assert_eq!("emptied".parse(), Ok(Allocation::Emptied));
+        assert!("first".parse::<Allocation>().is_err());
+
+        // 先手が自分の側をなくして終了
+        let mut board = Board::<U1, U2>::new(true);
+        board.sow(0);
+        assert_eq!(
+            board.clone().with_allocation(Allocation::Owner).scores(),
+            (1, 3)
+        );
+        assert_eq!(
+            board.clone().with_allocation(Allocation::Emptied).scores(),
+            (4, 0)
+        );
+        assert_eq!(
+            board.clone().with_allocation(Allocation::Opponent).scores(),
+            (1, 3)
+        );
+        let board = board.with_allocation(Allocation::Emptied);
+        assert_eq!((board.store_score(), board.pit_score()), (-1, -3));
+
+        // 先手が石を取って後手の側をなくして終了。残りの石は後手の相手の先手のもの
+        let mut board = Board::<U3, U1>::new(true).with_pits(&[1, 0, 1], &[0, 1, 0]);
+        board.sow(0);
+        assert!(board.is_finished());
+        assert_eq!(
+            board.clone().with_allocation(Allocation::Owner).scores(),
+            (3, 0)
+        );
+        assert_eq!(
+            board.clone().with_allocation(Allocation::Emptied).scores(),
+            (2, 1)
+        );
+        assert_eq!(
+            board.clone().with_allocation(Allocation::Opponent).scores(),
+            (3, 0)
+        );
+
+        // 終了していなければそれぞれの側のもの
+        let board = Board::<U2, U1>::new(true);
+        for &allocation in &[Allocation::Emptied, Allocation::Opponent] {
+            assert_eq!(board.clone().with_allocation(allocation).scores(), (2, 2));
+        }
+    }
 }
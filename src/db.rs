@@ -0,0 +1,436 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io::{self, Read, Write};
+
+use fnv::FnvHasher;
+
+use crate::{Allocation, Position, Ruleset, Settlement, WinCondition};
+
+pub const MAGIC: [u8; 4] = *b"MNCL";
+pub const VERSION: u16 = 1;
+/// ヘッダのバイト数
+pub const HEADER_SIZE: usize = 32;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DbKind {
+    /// `search_clean` の結果
+    Clean,
+    /// `search_score` の結果
+    Score,
+    /// `Tablebase`。キーを持たず評価値だけを番号順に並べ、石の数は穴に残っている石の最大数
+    Tablebase,
+}
+
+impl DbKind {
+    fn as_u8(self) -> u8 {
+        match self {
+            DbKind::Clean => 0,
+            DbKind::Score => 1,
+            DbKind::Tablebase => 2,
+        }
+    }
+
+    fn from_u8(v: u8) -> io::Result<DbKind> {
+        match v {
+            0 => Ok(DbKind::Clean),
+            1 => Ok(DbKind::Score),
+            2 => Ok(DbKind::Tablebase),
+            _ => Err(invalid_data(format!("不明なDBの種類です: {}", v))),
+        }
+    }
+}
+
+impl Allocation {
+    fn as_u8(self) -> u8 {
+        match self {
+            Allocation::Owner => 0,
+            Allocation::Emptied => 1,
+            Allocation::Opponent => 2,
+        }
+    }
+
+    fn from_u8(v: u8) -> io::Result<Allocation> {
+        match v {
+            0 => Ok(Allocation::Owner),
+            1 => Ok(Allocation::Emptied),
+            2 => Ok(Allocation::Opponent),
+            _ => Err(invalid_data(format!("不明な残りの石の分け方です: {}", v))),
+        }
+    }
+}
+
+impl WinCondition {
+    fn as_u8(self) -> u8 {
+        match self {
+            WinCondition::Score => 0,
+            WinCondition::Empty => 1,
+            WinCondition::Misere => 2,
+        }
+    }
+
+    fn from_u8(v: u8) -> io::Result<WinCondition> {
+        match v {
+            0 => Ok(WinCondition::Score),
+            1 => Ok(WinCondition::Empty),
+            2 => Ok(WinCondition::Misere),
+            _ => Err(invalid_data(format!("不明な勝利条件です: {}", v))),
+        }
+    }
+}
+
+/// DBファイルの先頭に置く情報
+///
+/// | offset | size | 内容 |
+/// |-------:|-----:|------|
+/// |      0 |    4 | `MAGIC` |
+/// |      4 |    2 | バージョン |
+/// |      6 |    1 | DBの種類 |
+/// |      7 |    1 | キーのバイト数 |
+/// |      8 |    1 | 値のバイト数 |
+/// |      9 |    1 | 穴の数 |
+/// |     10 |    1 | 石の数 |
+/// |     11 |    1 | 石取りの有無 |
+/// |     12 |    1 | 勝利条件 |
+/// |     13 |    1 | ルールの番号 (`Ruleset::code`) |
+/// |     14 |    1 | 残りの石の分け方 |
+/// |     15 |    1 | 予約 |
+/// |     16 |    8 | エントリ数 |
+/// |     24 |    8 | エントリ部分の FNV-1a ハッシュ |
+///
+/// 数値は全てリトルエンディアンで、エントリはキーの昇順に並ぶ
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Header {
+    pub kind: DbKind,
+    pub key_width: u8,
+    pub value_width: u8,
+    pub pits: u8,
+    pub seeds: u8,
+    pub stealing: bool,
+    pub win: WinCondition,
+    pub rules: u8,
+    pub allocation: Allocation,
+    pub len: u64,
+    pub checksum: u64,
+}
+
+impl Header {
+    pub(crate) fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut buf = [0; HEADER_SIZE];
+        buf[0..4].copy_from_slice(&MAGIC);
+        buf[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        buf[6] = self.kind.as_u8();
+        buf[7] = self.key_width;
+        buf[8] = self.value_width;
+        buf[9] = self.pits;
+        buf[10] = self.seeds;
+        buf[11] = self.stealing as u8;
+        buf[12] = self.win.as_u8();
+        buf[13] = self.rules;
+        buf[14] = self.allocation.as_u8();
+        buf[16..24].copy_from_slice(&self.len.to_le_bytes());
+        buf[24..32].copy_from_slice(&self.checksum.to_le_bytes());
+        buf
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> io::Result<Header> {
+        if buf.len() < HEADER_SIZE {
+            return Err(invalid_data("ヘッダが短すぎます".to_string()));
+        }
+        if buf[0..4] != MAGIC {
+            return Err(invalid_data("DBファイルではありません".to_string()));
+        }
+        let mut version = [0; 2];
+        version.copy_from_slice(&buf[4..6]);
+        let version = u16::from_le_bytes(version);
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "対応していないバージョンです: {}",
+                version
+            )));
+        }
+        let mut len = [0; 8];
+        len.copy_from_slice(&buf[16..24]);
+        let mut checksum = [0; 8];
+        checksum.copy_from_slice(&buf[24..32]);
+        Ok(Header {
+            kind: DbKind::from_u8(buf[6])?,
+            key_width: buf[7],
+            value_width: buf[8],
+            pits: buf[9],
+            seeds: buf[10],
+            stealing: buf[11] != 0,
+            win: WinCondition::from_u8(buf[12])?,
+            rules: buf[13],
+            allocation: Allocation::from_u8(buf[14])?,
+            len: u64::from_le_bytes(len),
+            checksum: u64::from_le_bytes(checksum),
+        })
+    }
+
+    /// `board` と同じ設定で作られたDBか確認する
+    pub fn check<B: Position>(&self, board: &B) -> io::Result<()> {
+        let (pits, seeds, stealing) = board.triple();
+        let win = board.win_condition();
+        let rules = board.rules().code();
+        let allocation = board.allocation();
+        if usize::from(self.pits) != pits
+            || usize::from(self.seeds) != seeds
+            || self.stealing != stealing
+            || self.win != win
+            || self.rules != rules
+            || self.allocation != allocation
+        {
+            return Err(invalid_data(format!(
+                "盤面の設定が違います: file=({}, {}, {}, {:?}, {}, {:?}) board=({}, {}, {}, {:?}, {}, {:?})",
+                self.pits,
+                self.seeds,
+                self.stealing,
+                self.win,
+                self.rules,
+                self.allocation,
+                pits,
+                seeds,
+                stealing,
+                win,
+                rules,
+                allocation
+            )));
+        }
+        Ok(())
+    }
+
+    /// キーが `K` で値が `V` のDBか確認する
+    pub fn expect<K: DbKey, V: DbValue>(&self) -> io::Result<()> {
+        if self.kind != V::KIND {
+            return Err(invalid_data(format!("DBの種類が違います: {:?}", self.kind)));
+        }
+        if usize::from(self.key_width) != K::WIDTH || usize::from(self.value_width) != V::WIDTH {
+            return Err(invalid_data(format!(
+                "キーか値のバイト数が違います: key={} value={}",
+                self.key_width, self.value_width
+            )));
+        }
+        Ok(())
+    }
+
+    /// エントリ1つあたりのバイト数
+    pub fn entry_width(&self) -> usize {
+        usize::from(self.key_width) + usize::from(self.value_width)
+    }
+}
+
+pub(crate) fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// ファイルに保存できるキー
+pub trait DbKey: Copy + Hash + Eq + Ord {
+    const WIDTH: usize;
+    fn write_to(self, buf: &mut Vec<u8>);
+    fn read_from(buf: &[u8]) -> Self;
+}
+
+macro_rules! impl_db_key {
+    ($($t:ty),*) => {
+        $(
+            impl DbKey for $t {
+                const WIDTH: usize = std::mem::size_of::<$t>();
+
+                fn write_to(self, buf: &mut Vec<u8>) {
+                    buf.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_from(buf: &[u8]) -> Self {
+                    let mut bytes = [0; std::mem::size_of::<$t>()];
+                    bytes.copy_from_slice(&buf[..Self::WIDTH]);
+                    <$t>::from_le_bytes(bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_db_key!(u16, u32, u64, u128);
+
+/// ファイルに保存できる評価値
+pub trait DbValue: Copy {
+    const KIND: DbKind;
+    const WIDTH: usize;
+    fn write_to(self, buf: &mut Vec<u8>);
+    fn read_from(buf: &[u8]) -> io::Result<Self>;
+}
+
+impl DbValue for i8 {
+    const KIND: DbKind = DbKind::Score;
+    const WIDTH: usize = 1;
+
+    fn write_to(self, buf: &mut Vec<u8>) {
+        buf.push(self as u8);
+    }
+
+    fn read_from(buf: &[u8]) -> io::Result<Self> {
+        Ok(buf[0] as i8)
+    }
+}
+
+impl DbValue for Settlement {
+    const KIND: DbKind = DbKind::Clean;
+    const WIDTH: usize = 2;
+
+    fn write_to(self, buf: &mut Vec<u8>) {
+        match self {
+            Settlement::Win(n) => buf.extend_from_slice(&[0, n]),
+            Settlement::Lose(n) => buf.extend_from_slice(&[1, n]),
+            Settlement::Draw => buf.extend_from_slice(&[2, 0]),
+        }
+    }
+
+    fn read_from(buf: &[u8]) -> io::Result<Self> {
+        match buf[0] {
+            0 => Ok(Settlement::Win(buf[1])),
+            1 => Ok(Settlement::Lose(buf[1])),
+            2 => Ok(Settlement::Draw),
+            t => Err(invalid_data(format!("不明な勝敗です: {}", t))),
+        }
+    }
+}
+
+/// 探索結果を引く操作
+pub trait DbLookup<K, V> {
+    fn lookup(&self, key: &K) -> Option<V>;
+}
+
+impl<K, V, H> DbLookup<K, V> for HashMap<K, V, H>
+where
+    K: Hash + Eq,
+    V: Copy,
+    H: BuildHasher,
+{
+    fn lookup(&self, key: &K) -> Option<V> {
+        self.get(key).copied()
+    }
+}
+
+pub(crate) fn checksum(body: &[u8]) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(body);
+    hasher.finish()
+}
+
+/// `board` の設定で探索したDBを書き出す
+pub fn save_db<W, B, K, V>(mut writer: W, board: &B, db: &HashMap<K, V>) -> io::Result<()>
+where
+    W: Write,
+    B: Position,
+    K: DbKey,
+    V: DbValue,
+{
+    let mut entries = db.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|(k, _)| **k);
+    let mut body = Vec::with_capacity(entries.len() * (K::WIDTH + V::WIDTH));
+    for (k, v) in entries {
+        k.write_to(&mut body);
+        v.write_to(&mut body);
+    }
+    let (pits, seeds, stealing) = board.triple();
+    let header = Header {
+        kind: V::KIND,
+        key_width: K::WIDTH as u8,
+        value_width: V::WIDTH as u8,
+        pits: pits as u8,
+        seeds: seeds as u8,
+        stealing,
+        win: board.win_condition(),
+        rules: board.rules().code(),
+        allocation: board.allocation(),
+        len: db.len() as u64,
+        checksum: checksum(&body),
+    };
+    writer.write_all(&header.to_bytes())?;
+    writer.write_all(&body)?;
+    writer.flush()
+}
+
+/// `save_db` で書き出したDBを読み込む
+pub fn load_db<R, K, V>(mut reader: R) -> io::Result<(Header, HashMap<K, V>)>
+where
+    R: Read,
+    K: DbKey,
+    V: DbValue,
+{
+    let mut buf = [0; HEADER_SIZE];
+    reader.read_exact(&mut buf)?;
+    let header = Header::from_bytes(&buf)?;
+    header.expect::<K, V>()?;
+    let mut body = Vec::with_capacity(header.len as usize * header.entry_width());
+    reader.read_to_end(&mut body)?;
+    if body.len() as u64 != header.len * header.entry_width() as u64 {
+        return Err(invalid_data("エントリ数が合いません".to_string()));
+    }
+    if checksum(&body) != header.checksum {
+        return Err(invalid_data("チェックサムが一致しません".to_string()));
+    }
+    let mut db = HashMap::with_capacity(header.len as usize);
+    for entry in body.chunks(header.entry_width()) {
+        let key = K::read_from(entry);
+        let value = V::read_from(&entry[K::WIDTH..])?;
+        db.insert(key, value);
+    }
+    Ok((header, db))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search_clean, search_score, settlement_key, Board, WinCondition};
+    use typenum::{U2, U3};
+
+    #[test]
+    fn round_trip_score() {
+        let board = Board::<U3, U2>::new(true);
+        let db: HashMap<_, _> = search_score(board.clone(), 2, 16).into();
+        let mut buf = Vec::new();
+        save_db(&mut buf, &board, &db).unwrap();
+        assert_eq!(buf.len(), HEADER_SIZE + db.len() * (8 + 1));
+
+        let (header, loaded) = load_db::<_, u64, i8>(buf.as_slice()).unwrap();
+        assert_eq!(header.kind, DbKind::Score);
+        assert_eq!(header.len, db.len() as u64);
+        header.check(&board).unwrap();
+        assert_eq!(loaded, db);
+    }
+
+    #[test]
+    fn round_trip_clean() {
+        let board = Board::<U2, U2>::new(false);
+        let db: HashMap<_, _> = search_clean(board.clone(), 2, 16).into();
+        let mut buf = Vec::new();
+        save_db(&mut buf, &board, &db).unwrap();
+
+        let (header, loaded) = load_db::<_, u128, Settlement>(buf.as_slice()).unwrap();
+        assert_eq!(header.kind, DbKind::Clean);
+        assert_eq!(loaded, db);
+        let key = settlement_key(&board);
+        assert_eq!(loaded.get(&key), db.get(&key));
+    }
+
+    #[test]
+    fn reject_broken() {
+        let board = Board::<U2, U2>::new(true);
+        let db: HashMap<_, _> = search_score(board.clone(), 2, 16).into();
+        let mut buf = Vec::new();
+        save_db(&mut buf, &board, &db).unwrap();
+
+        let mut broken = buf.clone();
+        *broken.last_mut().unwrap() ^= 1;
+        assert!(load_db::<_, u64, i8>(broken.as_slice()).is_err());
+        assert!(load_db::<_, u128, Settlement>(buf.as_slice()).is_err());
+        assert!(load_db::<_, u32, i8>(buf.as_slice()).is_err());
+
+        let (header, _) = load_db::<_, u64, i8>(buf.as_slice()).unwrap();
+        assert!(header.check(&Board::<U2, U2>::new(false)).is_err());
+        assert!(header.check(&Board::<U2, U3>::new(true)).is_err());
+        let misere = Board::<U2, U2>::new(true).with_win_condition(WinCondition::Misere);
+        assert!(header.check(&misere).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::rank::{index_size, rank, MAX_PITS};
+use crate::rules::sow_seeds;
+use crate::{Allocation, CompactKey, Kalah, Position, Renderer, Ruleset, Side, WinCondition};
+
+/// 穴の数と石の数を実行時に決める盤面
+#[derive(Debug, Clone)]
+pub struct DynBoard<R = Kalah> {
+    rules: R,
+    win: WinCondition,
+    allocation: Allocation,
+    seeds: usize,
+    pub side: Side,
+    pits: [Vec<u8>; 2],
+    stores: [u8; 2],
+}
+
+impl<R> Hash for DynBoard<R> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.side.hash(state);
+        self.pits[0].hash(state);
+        self.pits[1].hash(state);
+        self.stores.hash(state);
+    }
+}
+
+impl<R> PartialEq for DynBoard<R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.side == other.side && self.pits == other.pits && self.stores == other.stores
+    }
+}
+
+impl<R> Eq for DynBoard<R> {}
+
+impl<R: Ruleset> fmt::Display for DynBoard<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&Renderer::default().render(self))
+    }
+}
+
+impl DynBoard {
+    /// カラハのルールで、勝利条件はスコア性
+    pub fn new(pits: usize, seeds: usize, stealing: bool) -> DynBoard {
+        DynBoard::with_rules(pits, seeds, Kalah::new(stealing))
+    }
+}
+
+impl<R: Ruleset> DynBoard<R> {
+    /// 勝利条件はスコア性
+    pub fn with_rules(pits: usize, seeds: usize, rules: R) -> DynBoard<R> {
+        assert!(pits > 0, "穴の数は1以上にしてください");
+        assert!(
+            2 * pits * seeds <= usize::from(u8::MAX),
+            "石の総数が多すぎます"
+        );
+        assert!(pits <= MAX_PITS, "穴の数は{}以下にしてください", MAX_PITS);
+        assert!(
+            index_size(pits, 2 * pits * seeds).is_some(),
+            "キーが64bitに収まりません"
+        );
+        DynBoard {
+            rules,
+            win: WinCondition::Score,
+            allocation: Allocation::Owner,
+            seeds,
+            side: Side::First,
+            pits: [vec![seeds as u8; pits], vec![seeds as u8; pits]],
+            stores: [0, 0],
+        }
+    }
+
+    pub fn with_win_condition(mut self, win: WinCondition) -> DynBoard<R> {
+        self.win = win;
+        self
+    }
+
+    pub fn with_allocation(mut self, allocation: Allocation) -> DynBoard<R> {
+        self.allocation = allocation;
+        self
+    }
+}
+
+impl<R: Ruleset> Position for DynBoard<R> {
+    type Rules = R;
+
+    fn triple(&self) -> (usize, usize, bool) {
+        (self.pits[0].len(), self.seeds, self.rules.stealing())
+    }
+
+    fn rules(&self) -> &R {
+        &self.rules
+    }
+
+    fn win_condition(&self) -> WinCondition {
+        self.win
+    }
+
+    fn allocation(&self) -> Allocation {
+        self.allocation
+    }
+
+    fn side(&self) -> Side {
+        self.side
+    }
+
+    fn pits(&self, side: Side) -> &[u8] {
+        &self.pits[side.as_usize()]
+    }
+
+    fn stores(&self) -> [u8; 2] {
+        self.stores
+    }
+
+    fn with_pits(&self, self_pits: &[u8], opposite_pits: &[u8]) -> Self {
+        assert_eq!(self_pits.len(), self.pits[0].len());
+        assert_eq!(opposite_pits.len(), self.pits[1].len());
+        DynBoard {
+            rules: self.rules.clone(),
+            win: self.win,
+            allocation: self.allocation,
+            seeds: self.seeds,
+            side: Side::First,
+            pits: [self_pits.to_vec(), opposite_pits.to_vec()],
+            stores: [0, 0],
+        }
+    }
+
+    fn with_stores(&self, stores: [u8; 2], side: Side) -> Self {
+        DynBoard {
+            stores,
+            side,
+            ..self.clone()
+        }
+    }
+
+    fn sow(&mut self, pos: usize) {
+        let [first, second] = &mut self.pits;
+        self.side = sow_seeds(
+            &self.rules,
+            [first, second],
+            &mut self.stores,
+            self.side,
+            pos,
+        );
+    }
+}
+
+impl<R: Ruleset> CompactKey for DynBoard<R> {
+    type Key = u64;
+    fn key(&self) -> Self::Key {
+        rank(self.self_pits().iter().chain(self.opposite_pits()).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoke_1_2() {
+        let mut board = DynBoard::new(1, 2, true);
+        assert!(!board.is_finished());
+        assert_eq!(board.list_next().len(), 1);
+        assert_eq!(board.scores(), (2, 2));
+        let key1 = board.key();
+        board.sow(0);
+        assert!(board.is_finished());
+        assert_eq!(board.scores(), (1, 3));
+        let key2 = board.key();
+        assert_ne!(key1, key2);
+    }
+
+    #[test]
+    fn smoke_3_1() {
+        let mut board = DynBoard::new(3, 1, true);
+        assert!(!board.is_finished());
+        assert_eq!(board.list_next().len(), 4);
+        assert_eq!(board.scores(), (3, 3));
+        let key1 = board.key();
+        board.sow(2);
+        board.sow(1);
+        assert!(!board.is_finished());
+        assert_eq!(board.scores(), (4, 2));
+        let key2 = board.key();
+        assert_ne!(key1, key2);
+    }
+
+    #[test]
+    fn triple() {
+        let board = DynBoard::new(6, 4, false);
+        assert_eq!(board.triple(), (6, 4, false));
+    }
+}
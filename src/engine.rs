@@ -0,0 +1,172 @@
+//! コンピュータの対局相手
+//!
+//! 探索結果の評価値で手番を選び、難しさに応じた確率で最善でない手番を選ぶ。
+
+use std::str::FromStr;
+
+use crate::Position;
+
+/// 種を決めれば同じ列を返す乱数。splitmix64
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// 0 以上 1 未満
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// 0 以上 `n` 未満
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// 最善でない手番を選ぶ確率
+///
+/// `easy` (0.5), `normal` (0.2), `hard` (0.05), `perfect` (0) か、0 から 1 の数で指定する
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Difficulty {
+    pub mistake: f64,
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mistake = match s {
+            "easy" => 0.5,
+            "normal" => 0.2,
+            "hard" => 0.05,
+            "perfect" => 0.0,
+            _ => match s.parse::<f64>() {
+                Ok(p) if (0.0..=1.0).contains(&p) => p,
+                _ => return Err(format!("不明な難しさです: {}", s)),
+            },
+        };
+        Ok(Difficulty { mistake })
+    }
+}
+
+/// 評価値を見て手番を選ぶ対局相手
+#[derive(Debug, Clone)]
+pub struct Engine {
+    pub difficulty: Difficulty,
+    rng: Rng,
+}
+
+impl Engine {
+    pub fn new(difficulty: Difficulty, seed: u64) -> Engine {
+        Engine {
+            difficulty,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// `board` の手番で続けて蒔く穴を選ぶ。終了した盤面なら `None`
+    ///
+    /// `value` は手番を終えた盤面の、`board` の手番側から見て大きいほど良い値を返す。
+    /// `mistake` の確率で最善より悪い手番から、それ以外は最善の手番から一様に選ぶ
+    pub fn choose<B, V, F>(&mut self, board: &B, mut value: F) -> Option<Vec<usize>>
+    where
+        B: Position,
+        V: Ord,
+        F: FnMut(&B) -> V,
+    {
+        let mut turns = board
+            .list_next_with_pos()
+            .into_iter()
+            .map(|(next, pos)| (value(&next), pos))
+            .collect::<Vec<_>>();
+        if turns.is_empty() {
+            return None;
+        }
+        // 乱数の種が同じなら同じ手番を選ぶように、同じ値は穴の添字順に並べる
+        turns.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        let best = turns.iter().take_while(|(v, _)| *v == turns[0].0).count();
+        let i = if best < turns.len() && self.rng.next_f64() < self.difficulty.mistake {
+            best + self.rng.below(turns.len() - best)
+        } else {
+            self.rng.below(best)
+        };
+        Some(turns.swap_remove(i).1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search_score, Board, CompactKey};
+    use typenum::U3;
+
+    #[test]
+    fn rng() {
+        let mut a = Rng::new(1);
+        let mut b = Rng::new(1);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+            let x = a.next_f64();
+            assert!((0.0..1.0).contains(&x));
+            assert!(a.below(3) < 3);
+            b.next_f64();
+            b.below(3);
+        }
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+
+    #[test]
+    fn difficulty() {
+        assert_eq!("perfect".parse(), Ok(Difficulty { mistake: 0.0 }));
+        assert_eq!("0.3".parse(), Ok(Difficulty { mistake: 0.3 }));
+        assert!("1.5".parse::<Difficulty>().is_err());
+        assert!("godlike".parse::<Difficulty>().is_err());
+    }
+
+    #[test]
+    fn choose() {
+        let board = Board::<U3, U3>::new(true);
+        let db = search_score(board.clone(), 1, 16);
+        let value = |b: &Board<U3, U3>| -(b.store_score() + db.get(&b.key()).unwrap());
+        let best = board.list_next_with_pos().keys().map(&value).max().unwrap();
+        let score_of = |turn: &[usize]| {
+            let mut b = board.clone();
+            for pos in turn {
+                b.sow(*pos);
+            }
+            value(&b)
+        };
+
+        let mut perfect = Engine::new("perfect".parse().unwrap(), 0);
+        let mut clumsy = Engine::new(Difficulty { mistake: 1.0 }, 0);
+        for _ in 0..20 {
+            assert_eq!(score_of(&perfect.choose(&board, value).unwrap()), best);
+            assert!(score_of(&clumsy.choose(&board, value).unwrap()) < best);
+        }
+
+        let mut a = Engine::new("easy".parse().unwrap(), 7);
+        let mut b = Engine::new("easy".parse().unwrap(), 7);
+        for _ in 0..20 {
+            assert_eq!(a.choose(&board, value), b.choose(&board, value));
+        }
+
+        let mut finished = board.clone();
+        while let Some(turn) = perfect.choose(&finished, value) {
+            for pos in turn {
+                finished.sow(pos);
+            }
+        }
+        assert!(finished.is_finished());
+    }
+}
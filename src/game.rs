@@ -0,0 +1,261 @@
+//! 対局の記録
+//!
+//! 蒔いた手を1手ずつ木に記録する。戻した後に別の手を蒔くと元の手順を残したまま分岐になる。
+
+use crate::{Position, Side};
+
+#[derive(Debug, Clone)]
+struct Node<B> {
+    board: B,
+    /// 親の盤面と、そこで蒔いた穴。最初の盤面では `None`
+    parent: Option<(usize, usize)>,
+    children: Vec<usize>,
+    /// `redo` で進む子。最後に通った子
+    next: Option<usize>,
+}
+
+/// 最初の盤面から蒔いた手を記録し、戻したり進めたりできる対局
+///
+/// もう一度の手番で続けて蒔く場合も1回の `play` が1手 (ply) になる
+#[derive(Debug, Clone)]
+pub struct Game<B> {
+    nodes: Vec<Node<B>>,
+    current: usize,
+}
+
+impl<B: Position> Game<B> {
+    pub fn new(board: B) -> Game<B> {
+        Game {
+            nodes: vec![Node {
+                board,
+                parent: None,
+                children: Vec::new(),
+                next: None,
+            }],
+            current: 0,
+        }
+    }
+
+    /// 最初の盤面
+    pub fn root(&self) -> &B {
+        &self.nodes[0].board
+    }
+
+    /// 今の盤面
+    pub fn board(&self) -> &B {
+        &self.nodes[self.current].board
+    }
+
+    /// 最初の盤面から今の盤面までの手数
+    pub fn ply(&self) -> usize {
+        self.path().len() - 1
+    }
+
+    /// 今の盤面で `pos` の穴を蒔く
+    ///
+    /// 同じ手が記録されていればそれを辿り、なければ新しい分岐を作る
+    pub fn play(&mut self, pos: usize) -> Result<(), String> {
+        let node = &self.nodes[self.current];
+        if node.board.is_finished() {
+            return Err("ゲームは終了しています".to_string());
+        }
+        node.board.can_sow(pos)?;
+        let child = match self.child(pos) {
+            Some(child) => child,
+            None => {
+                let mut board = node.board.clone();
+                board.sow(pos);
+                self.nodes.push(Node {
+                    board,
+                    parent: Some((self.current, pos)),
+                    children: Vec::new(),
+                    next: None,
+                });
+                let child = self.nodes.len() - 1;
+                self.nodes[self.current].children.push(child);
+                child
+            }
+        };
+        self.nodes[self.current].next = Some(child);
+        self.current = child;
+        Ok(())
+    }
+
+    /// 1手戻す。最初の盤面なら `false`
+    pub fn undo(&mut self) -> bool {
+        match self.nodes[self.current].parent {
+            Some((parent, _)) => {
+                self.current = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 最後に戻した手順を1手進める。進める手がなければ `false`
+    pub fn redo(&mut self) -> bool {
+        match self.nodes[self.current].next {
+            Some(next) => {
+                self.current = next;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 今の手順の `ply` 手目の盤面に移る
+    ///
+    /// 今より前なら戻し、後なら `redo` で進む手順を辿る
+    pub fn jump(&mut self, ply: usize) -> Result<(), String> {
+        let path = self.line();
+        match path.get(ply) {
+            Some(&node) => {
+                self.current = node;
+                Ok(())
+            }
+            None => Err(format!("0から{}の間で指定してください", path.len() - 1)),
+        }
+    }
+
+    /// 今の盤面で記録されている手。最初に蒔いた順
+    pub fn variations(&self) -> Vec<usize> {
+        self.nodes[self.current]
+            .children
+            .iter()
+            .map(|&child| self.nodes[child].parent.unwrap().1)
+            .collect()
+    }
+
+    /// 最初の盤面から今の盤面までに蒔いた穴
+    pub fn history(&self) -> Vec<usize> {
+        self.moves(&self.path())
+    }
+
+    /// `history` を手番ごとにまとめたもの。もう一度の手番で続けて蒔いた穴は同じ手番に入る
+    pub fn turns(&self) -> Vec<(Side, Vec<usize>)> {
+        let path = self.path();
+        let mut turns: Vec<(Side, Vec<usize>)> = Vec::new();
+        for w in path.windows(2) {
+            let side = self.nodes[w[0]].board.side();
+            let pos = self.nodes[w[1]].parent.unwrap().1;
+            match turns.last_mut() {
+                Some((last, list)) if *last == side => list.push(pos),
+                _ => turns.push((side, vec![pos])),
+            }
+        }
+        turns
+    }
+
+    /// 最初の盤面から今の盤面までの盤面
+    pub fn boards(&self) -> Vec<&B> {
+        self.path().iter().map(|&i| &self.nodes[i].board).collect()
+    }
+
+    /// 今の手順を `redo` で進めた最後までに蒔いた穴
+    pub fn line_moves(&self) -> Vec<usize> {
+        self.moves(&self.line())
+    }
+
+    fn child(&self, pos: usize) -> Option<usize> {
+        self.nodes[self.current]
+            .children
+            .iter()
+            .cloned()
+            .find(|&child| self.nodes[child].parent.unwrap().1 == pos)
+    }
+
+    /// 最初の盤面から今の盤面までのノード
+    fn path(&self) -> Vec<usize> {
+        let mut path = vec![self.current];
+        while let Some((parent, _)) = self.nodes[*path.last().unwrap()].parent {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+
+    /// `path` に `redo` で進むノードを続けたもの
+    fn line(&self) -> Vec<usize> {
+        let mut line = self.path();
+        while let Some(next) = self.nodes[*line.last().unwrap()].next {
+            line.push(next);
+        }
+        line
+    }
+
+    fn moves(&self, nodes: &[usize]) -> Vec<usize> {
+        nodes[1..]
+            .iter()
+            .map(|&i| self.nodes[i].parent.unwrap().1)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Board;
+    use typenum::U3;
+
+    #[test]
+    fn undo_redo() {
+        let mut game = Game::new(Board::<U3, U3>::new(true));
+        // 0 はストアで終わるので先手がもう一度蒔く
+        game.play(0).unwrap();
+        game.play(1).unwrap();
+        game.play(2).unwrap();
+        assert_eq!(game.ply(), 3);
+        assert_eq!(game.history(), vec![0, 1, 2]);
+        assert_eq!(
+            game.turns(),
+            vec![(Side::First, vec![0, 1]), (Side::Second, vec![2])]
+        );
+        let last = game.board().clone();
+
+        assert!(game.undo());
+        assert!(game.undo());
+        assert_eq!(game.history(), vec![0]);
+        assert!(game.redo());
+        assert!(game.redo());
+        assert!(!game.redo());
+        assert_eq!(game.board(), &last);
+
+        game.jump(0).unwrap();
+        assert_eq!(game.board(), game.root());
+        assert!(!game.undo());
+        game.jump(3).unwrap();
+        assert_eq!(game.board(), &last);
+        assert!(game.jump(4).is_err());
+
+        let mut board = Board::<U3, U3>::new(true);
+        for pos in game.history() {
+            board.sow(pos);
+        }
+        assert_eq!(game.board(), &board);
+        assert_eq!(game.boards().len(), 4);
+    }
+
+    #[test]
+    fn variations() {
+        let mut game = Game::new(Board::<U3, U3>::new(true));
+        game.play(1).unwrap();
+        game.play(0).unwrap();
+        game.jump(0).unwrap();
+        assert_eq!(game.line_moves(), vec![1, 0]);
+
+        // 別の手を蒔くと元の手順は分岐として残る
+        game.play(2).unwrap();
+        game.undo();
+        assert_eq!(game.variations(), vec![1, 2]);
+        assert_eq!(game.line_moves(), vec![2]);
+
+        // 記録されている手を蒔くとその先の手順に戻る
+        game.play(1).unwrap();
+        assert!(game.redo());
+        assert_eq!(game.history(), vec![1, 0]);
+        assert_eq!(game.variations(), vec![]);
+
+        assert!(game.play(5).is_err());
+        assert_eq!(game.history(), vec![1, 0]);
+    }
+}
@@ -1,5 +1,27 @@
 mod board;
+mod db;
+mod dyn_board;
+mod engine;
+mod game;
+mod mapped_db;
+mod notation;
+mod protocol;
+mod rank;
+mod record;
+mod render;
+mod rules;
 mod search;
 
 pub use board::*;
+pub use db::*;
+pub use dyn_board::*;
+pub use engine::*;
+pub use game::*;
+pub use mapped_db::*;
+pub use notation::*;
+pub use protocol::*;
+pub use rank::*;
+pub use record::*;
+pub use render::*;
+pub use rules::*;
 pub use search::*;
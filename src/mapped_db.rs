@@ -0,0 +1,169 @@
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::db::{checksum, invalid_data};
+use crate::{DbKey, DbLookup, DbValue, Header, HEADER_SIZE};
+
+/// `save_db` で書き出したファイルをメモリマップして読み出すDB
+///
+/// エントリはキーの昇順に並んでいるので二分探索で引く
+pub struct MappedDb<K, V> {
+    header: Header,
+    mmap: Mmap,
+    _entry: PhantomData<(K, V)>,
+}
+
+impl<K, V> MappedDb<K, V>
+where
+    K: DbKey,
+    V: DbValue,
+{
+    /// ヘッダとファイルサイズだけを確認して開く
+    pub fn open<T: AsRef<Path>>(path: T) -> io::Result<MappedDb<K, V>> {
+        let file = File::open(path)?;
+        // 読み出し専用で開き、書き換えないことを前提にする
+        let mmap = unsafe { Mmap::map(&file)? };
+        let header = Header::from_bytes(&mmap)?;
+        header.expect::<K, V>()?;
+        let body_len = (mmap.len() - HEADER_SIZE) as u64;
+        if body_len != header.len * header.entry_width() as u64 {
+            return Err(invalid_data("エントリ数が合いません".to_string()));
+        }
+        Ok(MappedDb {
+            header,
+            mmap,
+            _entry: PhantomData,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn len(&self) -> usize {
+        self.header.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.header.len == 0
+    }
+
+    /// 全体を読んでチェックサムを確認する
+    pub fn verify(&self) -> io::Result<()> {
+        if checksum(&self.mmap[HEADER_SIZE..]) != self.header.checksum {
+            return Err(invalid_data("チェックサムが一致しません".to_string()));
+        }
+        Ok(())
+    }
+
+    fn entry(&self, i: usize) -> &[u8] {
+        let width = self.header.entry_width();
+        let start = HEADER_SIZE + i * width;
+        &self.mmap[start..start + width]
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut lo = 0;
+        let mut hi = self.len();
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let entry = self.entry(mid);
+            match K::read_from(entry).cmp(key) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => return V::read_from(&entry[K::WIDTH..]).ok(),
+            }
+        }
+        None
+    }
+}
+
+impl<K, V> DbLookup<K, V> for MappedDb<K, V>
+where
+    K: DbKey,
+    V: DbValue,
+{
+    fn lookup(&self, key: &K) -> Option<V> {
+        self.get(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{save_db, search_clean, search_score, settlement_key, Board, Settlement};
+    use std::collections::HashMap;
+    use std::env::temp_dir;
+    use std::fs::{remove_file, OpenOptions};
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::path::PathBuf;
+    use std::process;
+    use typenum::{U2, U3, U4};
+
+    /// 同時に走るテストと重ならないように、プロセス番号を付けた一時ファイル
+    fn temp_path(name: &str) -> PathBuf {
+        temp_dir().join(format!("mancala_mapped_db_{}_{}.db", name, process::id()))
+    }
+
+    #[test]
+    fn lookup_score() {
+        let board = Board::<U4, U2>::new(true);
+        let db: HashMap<_, _> = search_score(board.clone(), 2, 16).into();
+        let path = temp_path("lookup_score");
+        save_db(File::create(&path).unwrap(), &board, &db).unwrap();
+
+        let mapped = MappedDb::<u64, i8>::open(&path).unwrap();
+        mapped.verify().unwrap();
+        mapped.header().check(&board).unwrap();
+        assert_eq!(mapped.len(), db.len());
+        for (key, value) in db.iter() {
+            assert_eq!(mapped.get(key), Some(*value));
+        }
+        assert_eq!(mapped.get(&u64::MAX), None);
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn lookup_clean() {
+        let board = Board::<U3, U2>::new(false);
+        let db: HashMap<_, _> = search_clean(board.clone(), 2, 16).into();
+        let path = temp_path("lookup_clean");
+        save_db(File::create(&path).unwrap(), &board, &db).unwrap();
+
+        let mapped = MappedDb::<u128, Settlement>::open(&path).unwrap();
+        assert_eq!(
+            mapped.lookup(&settlement_key(&board)),
+            db.lookup(&settlement_key(&board))
+        );
+        assert!(MappedDb::<u64, i8>::open(&path).is_err());
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn verify_broken() {
+        let board = Board::<U3, U2>::new(true);
+        let db: HashMap<_, _> = search_score(board.clone(), 2, 16).into();
+        let path = temp_path("verify_broken");
+        save_db(File::create(&path).unwrap(), &board, &db).unwrap();
+        {
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&path)
+                .unwrap();
+            let mut last = [0];
+            file.seek(SeekFrom::End(-1)).unwrap();
+            file.read_exact(&mut last).unwrap();
+            file.seek(SeekFrom::End(-1)).unwrap();
+            file.write_all(&[last[0] ^ 1]).unwrap();
+        }
+        let mapped = MappedDb::<u64, i8>::open(&path).unwrap();
+        assert!(mapped.verify().is_err());
+        remove_file(&path).unwrap();
+    }
+}
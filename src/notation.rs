@@ -0,0 +1,156 @@
+//! 盤面の表記
+//!
+//! `3,3,3/3,3,3 0-0 F` のように、先手の穴、後手の穴をそれぞれ添字の小さい方から `,` で区切って
+//! `/` でつなぎ、空白の後に先手と後手のストアを `-` でつなぎ、最後に手番 (`F` か `S`) を書く。
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Side;
+
+/// 穴、ストア、手番だけを持つ盤面の表記。ルールや勝利条件は含まない
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Notation {
+    pub pits: [Vec<u8>; 2],
+    pub stores: [u8; 2],
+    pub side: Side,
+}
+
+impl Notation {
+    /// 穴とストアにある石の合計
+    pub fn seeds(&self) -> usize {
+        self.pits
+            .iter()
+            .flatten()
+            .chain(self.stores.iter())
+            .map(|s| usize::from(*s))
+            .sum()
+    }
+}
+
+fn parse_num(s: &str) -> Result<u8, String> {
+    s.trim()
+        .parse()
+        .map_err(|_| format!("石の数ではありません: {}", s))
+}
+
+fn parse_row(s: &str) -> Result<Vec<u8>, String> {
+    s.split(',').map(parse_num).collect()
+}
+
+impl FromStr for Notation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split_whitespace().collect::<Vec<_>>();
+        if parts.len() != 3 {
+            return Err(format!(
+                "`穴/穴 ストア-ストア 手番` の形で書いてください: {}",
+                s
+            ));
+        }
+        let rows = parts[0].split('/').collect::<Vec<_>>();
+        if rows.len() != 2 {
+            return Err(format!(
+                "穴の並びは `/` で2つに分けてください: {}",
+                parts[0]
+            ));
+        }
+        let pits = [parse_row(rows[0])?, parse_row(rows[1])?];
+        if pits[0].len() != pits[1].len() {
+            return Err(format!("両側の穴の数を揃えてください: {}", parts[0]));
+        }
+        let stores = parts[1].split('-').collect::<Vec<_>>();
+        if stores.len() != 2 {
+            return Err(format!("ストアは `-` でつないでください: {}", parts[1]));
+        }
+        let stores = [parse_num(stores[0])?, parse_num(stores[1])?];
+        let side = match parts[2] {
+            "F" => Side::First,
+            "S" => Side::Second,
+            _ => return Err(format!("手番は F か S にしてください: {}", parts[2])),
+        };
+        Ok(Notation { pits, stores, side })
+    }
+}
+
+impl fmt::Display for Notation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let row = |pits: &[u8]| {
+            pits.iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let side = match self.side {
+            Side::First => "F",
+            Side::Second => "S",
+        };
+        write!(
+            f,
+            "{}/{} {}-{} {}",
+            row(&self.pits[0]),
+            row(&self.pits[1]),
+            self.stores[0],
+            self.stores[1],
+            side
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search_score, Board, CompactKey, DynBoard, Position};
+    use typenum::{U2, U3};
+
+    #[test]
+    fn round_trip() {
+        let notation = "3,0,4/1,2,3 5-0 S".parse::<Notation>().unwrap();
+        assert_eq!(notation.pits, [vec![3, 0, 4], vec![1, 2, 3]]);
+        assert_eq!(notation.stores, [5, 0]);
+        assert_eq!(notation.side, Side::Second);
+        assert_eq!(notation.seeds(), 18);
+        assert_eq!(notation.to_string(), "3,0,4/1,2,3 5-0 S");
+
+        let mut board = Board::<U3, U3>::new(true);
+        assert_eq!(board.notation().to_string(), "3,3,3/3,3,3 0-0 F");
+        board.sow(1);
+        let parsed = board.with_notation(&board.notation()).unwrap();
+        assert_eq!(parsed, board);
+    }
+
+    #[test]
+    fn parse_error() {
+        for s in &[
+            "3,3,3/3,3,3 0-0",
+            "3,3,3/3,3 0-0 F",
+            "3,3,3|3,3,3 0-0 F",
+            "3,3,3/3,3,3 0:0 F",
+            "3,3,a/3,3,3 0-0 F",
+            "3,3,3/3,3,3 0-0 X",
+        ] {
+            assert!(s.parse::<Notation>().is_err(), "{}", s);
+        }
+        let board = Board::<U3, U2>::new(true);
+        let notation = "3,3/3,3 0-0 F".parse().unwrap();
+        assert!(board.with_notation(&notation).is_err());
+        let notation = "3,3,3/3,3,3 0-0 F".parse().unwrap();
+        assert!(board.with_notation(&notation).is_err());
+    }
+
+    #[test]
+    fn solve_from_notation() {
+        let board = Board::<U3, U2>::new(true);
+        let db = search_score(board.clone(), 2, 16);
+        for next in board.list_next() {
+            let notation = next.notation().to_string().parse().unwrap();
+            let from = board.with_notation(&notation).unwrap();
+            let key = from.key();
+            assert_eq!(search_score(from, 2, 16).get(&key), db.get(&next.key()));
+
+            let dyn_board = DynBoard::new(3, 2, true).with_notation(&notation).unwrap();
+            assert_eq!(dyn_board.notation(), notation);
+        }
+    }
+}
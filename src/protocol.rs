@@ -0,0 +1,273 @@
+//! 外部のプログラムから標準入出力で操作するための行単位のプロトコル
+//!
+//! 1行に1つのコマンドを受け取り、結果を1行以上で返す。失敗したら `error <理由>` を返す。
+//!
+//! | コマンド | 返す行 |
+//! |----------|--------|
+//! | `newgame <pits> <seeds> <stealing>` | 初期盤面にして探索結果を用意し `ok` |
+//! | `position startpos [moves <pos> ...]` | 初期盤面から穴を順に蒔いて `ok` |
+//! | `position <notation> [moves <pos> ...]` | `3,3,3/3,3,3 0-0 F` の盤面から蒔いて `ok` |
+//! | `go` | 最善の手番 `bestmove 0,1 value=4`。終了した盤面では `bestmove none value=...` |
+//! | `eval` | 手番ごとの `turn 0,1 value=4` を良い順に並べ、最後に今の盤面の `value=4` |
+//! | `quit` | 何も返さずに終わる |
+//!
+//! 評価値は今の盤面の手番側から見たスコアの差。`moves` の穴はもう一度の手番も1つずつ書く。
+
+use std::io::{self, BufRead, Write};
+
+use crate::{format_turn, CompactKey, DbLookup, Position};
+
+/// 盤面の探索結果
+pub type ScoreDb<B> = Box<dyn DbLookup<<B as CompactKey>::Key, i8>>;
+
+/// `newgame` で盤面を作る関数
+type NewBoard<B> = Box<dyn FnMut(usize, usize, bool) -> Result<B, String>>;
+
+/// 盤面から辿れる盤面の探索結果を用意する関数
+type Load<B> = Box<dyn FnMut(&B) -> Result<ScoreDb<B>, String>>;
+
+/// 手番側から見た評価値と、その手番で続けて蒔く穴
+type Turns = Vec<(i8, Vec<usize>)>;
+
+/// プロトコルの状態。初期盤面、今の盤面と探索結果を持つ
+pub struct Protocol<B: CompactKey> {
+    new_board: NewBoard<B>,
+    load: Load<B>,
+    game: Option<(B, B, ScoreDb<B>)>,
+}
+
+fn parse_arg<T: std::str::FromStr>(name: &str, arg: Option<&str>) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| format!("{} がありません", name))?;
+    arg.parse()
+        .map_err(|_| format!("{} が読めません: {}", name, arg))
+}
+
+impl<B> Protocol<B>
+where
+    B: Position + CompactKey + 'static,
+{
+    /// `new_board` は `newgame` の引数から盤面を作り、`load` は渡した盤面から辿れる盤面の探索結果を返す
+    pub fn new<N, L>(new_board: N, load: L) -> Protocol<B>
+    where
+        N: FnMut(usize, usize, bool) -> Result<B, String> + 'static,
+        L: FnMut(&B) -> Result<ScoreDb<B>, String> + 'static,
+    {
+        Protocol {
+            new_board: Box::new(new_board),
+            load: Box::new(load),
+            game: None,
+        }
+    }
+
+    /// 1行のコマンドを実行して返す行。`quit` なら `None`
+    pub fn execute(&mut self, line: &str) -> Option<Vec<String>> {
+        let mut args = line.split_whitespace();
+        let result = match args.next() {
+            None => return Some(Vec::new()),
+            Some("quit") => return None,
+            Some("newgame") => self.newgame(args),
+            Some("position") => self.position(args),
+            Some("go") => self.go(),
+            Some("eval") => self.eval(),
+            Some(command) => Err(format!("不明なコマンドです: {}", command)),
+        };
+        Some(result.unwrap_or_else(|e| vec![format!("error {}", e)]))
+    }
+
+    /// `input` が終わるか `quit` を受け取るまでコマンドを実行する
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        for line in input.lines() {
+            match self.execute(&line?) {
+                Some(lines) => {
+                    for l in lines {
+                        writeln!(output, "{}", l)?;
+                    }
+                    output.flush()?;
+                }
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn newgame<'a, I: Iterator<Item = &'a str>>(
+        &mut self,
+        mut args: I,
+    ) -> Result<Vec<String>, String> {
+        let pits = parse_arg("pits", args.next())?;
+        let seeds = parse_arg("seeds", args.next())?;
+        let stealing = parse_arg("stealing", args.next())?;
+        let board = (self.new_board)(pits, seeds, stealing)?;
+        let db = (self.load)(&board)?;
+        self.game = Some((board.clone(), board, db));
+        Ok(vec!["ok".to_string()])
+    }
+
+    fn position<'a, I: Iterator<Item = &'a str>>(
+        &mut self,
+        args: I,
+    ) -> Result<Vec<String>, String> {
+        let (root, _, db) = self
+            .game
+            .as_ref()
+            .ok_or_else(|| "先に newgame を送ってください".to_string())?;
+        let args = args.collect::<Vec<_>>();
+        let (start, moves) = match args.iter().position(|a| *a == "moves") {
+            Some(i) => (&args[..i], &args[i + 1..]),
+            None => (&args[..], &args[args.len()..]),
+        };
+        let mut board = match start {
+            ["startpos"] => root.clone(),
+            [] => return Err("startpos か盤面を指定してください".to_string()),
+            _ => root.with_notation(&start.join(" ").parse()?)?,
+        };
+        for pos in moves {
+            let pos = parse_arg("moves", Some(pos))?;
+            if board.is_finished() {
+                return Err("ゲームは終了しています".to_string());
+            }
+            board.can_sow(pos)?;
+            board.sow(pos);
+        }
+        // 初期盤面から辿れない盤面なら探索し直す
+        if db.lookup(&board.key()).is_none() {
+            let db = (self.load)(&board)?;
+            if db.lookup(&board.key()).is_none() {
+                return Err("盤面の探索結果がありません".to_string());
+            }
+            self.game = Some((root.clone(), board, db));
+        } else if let Some((_, current, _)) = self.game.as_mut() {
+            *current = board;
+        }
+        Ok(vec!["ok".to_string()])
+    }
+
+    /// 今の盤面と、手番ごとの手番側から見た評価値。良い順に並べる
+    ///
+    /// 探索結果にない盤面があればエラー
+    fn turns(&self) -> Result<(i8, Turns), String> {
+        let (_, board, db) = self
+            .game
+            .as_ref()
+            .ok_or_else(|| "先に newgame を送ってください".to_string())?;
+        let value = |b: &B| {
+            db.lookup(&b.key())
+                .map(|score| b.store_score() + score)
+                .ok_or_else(|| "盤面の探索結果がありません".to_string())
+        };
+        let mut turns = board
+            .list_next_with_pos()
+            .into_iter()
+            .map(|(next, pos)| Ok((-value(&next)?, pos)))
+            .collect::<Result<Turns, String>>()?;
+        turns.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        Ok((value(board)?, turns))
+    }
+
+    fn go(&self) -> Result<Vec<String>, String> {
+        let (value, turns) = self.turns()?;
+        Ok(vec![match turns.first() {
+            Some((value, turn)) => format!("bestmove {} value={}", format_turn(turn), value),
+            None => format!("bestmove none value={}", value),
+        }])
+    }
+
+    fn eval(&self) -> Result<Vec<String>, String> {
+        let (value, turns) = self.turns()?;
+        let mut lines = turns
+            .iter()
+            .map(|(value, turn)| format!("turn {} value={}", format_turn(turn), value))
+            .collect::<Vec<_>>();
+        lines.push(format!("value={}", value));
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search_score, DynBoard};
+    use std::collections::HashMap;
+
+    fn protocol() -> Protocol<DynBoard> {
+        Protocol::new(
+            |pits, seeds, stealing| Ok(DynBoard::new(pits, seeds, stealing)),
+            |board: &DynBoard| {
+                let db: HashMap<_, _> = search_score(board.clone(), 1, 16).into();
+                Ok(Box::new(db) as ScoreDb<DynBoard>)
+            },
+        )
+    }
+
+    #[test]
+    fn session() {
+        let mut p = protocol();
+        let input = b"newgame 3 3 true\ngo\neval\nposition startpos moves 0 1\ngo\nquit\ngo\n";
+        let mut output = Vec::new();
+        p.run(&input[..], &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "ok");
+
+        let board = DynBoard::new(3, 3, true);
+        let db = search_score(board.clone(), 1, 16);
+        let value = db.get(&board.key()).unwrap();
+        assert!(lines[1].starts_with("bestmove "));
+        assert!(lines[1].ends_with(&format!(" value={}", value)));
+        // eval は手番の数だけ turn を返し、最後に今の盤面の評価値
+        let turns = board.list_next_with_pos().len();
+        assert!(lines[2..2 + turns].iter().all(|l| l.starts_with("turn ")));
+        assert_eq!(lines[2 + turns], format!("value={}", value));
+        assert_eq!(lines[2 + turns + 1], "ok");
+        assert!(lines[2 + turns + 2].starts_with("bestmove "));
+        // quit の後は読まない
+        assert_eq!(lines.len(), 2 + turns + 3);
+    }
+
+    #[test]
+    fn position() {
+        let mut p = protocol();
+        assert_eq!(
+            p.execute("go"),
+            Some(vec!["error 先に newgame を送ってください".to_string()])
+        );
+        assert_eq!(p.execute("newgame 2 2 false"), Some(vec!["ok".to_string()]));
+        // 初期盤面から辿れない盤面は探索し直す
+        assert_eq!(
+            p.execute("position 0,4/0,0 4-0 S"),
+            Some(vec!["ok".to_string()])
+        );
+        assert_eq!(
+            p.execute("go"),
+            Some(vec!["bestmove none value=-8".to_string()])
+        );
+        assert_eq!(
+            p.execute("position 1,1/2,1 3-0 F moves 1"),
+            Some(vec!["ok".to_string()])
+        );
+        assert!(p.execute("position startpos moves 5").unwrap()[0].starts_with("error "));
+        assert!(p.execute("position 1,1/2,1 F").unwrap()[0].starts_with("error "));
+        assert!(p.execute("foo").unwrap()[0].starts_with("error "));
+        // 失敗しても前の盤面は残る
+        assert!(p.execute("go").unwrap()[0].starts_with("bestmove "));
+        assert_eq!(p.execute(""), Some(vec![]));
+        assert_eq!(p.execute("quit"), None);
+    }
+
+    #[test]
+    fn missing_value() {
+        // 初期盤面の値しか持たない探索結果
+        let mut p = Protocol::new(
+            |pits, seeds, stealing| Ok(DynBoard::new(pits, seeds, stealing)),
+            |board: &DynBoard| {
+                let mut db = HashMap::<_, i8>::new();
+                db.insert(board.key(), 0);
+                Ok(Box::new(db) as ScoreDb<DynBoard>)
+            },
+        );
+        assert_eq!(p.execute("newgame 2 2 false"), Some(vec!["ok".to_string()]));
+        let error = Some(vec!["error 盤面の探索結果がありません".to_string()]);
+        assert_eq!(p.execute("go"), error);
+        assert_eq!(p.execute("eval"), error);
+    }
+}
@@ -0,0 +1,164 @@
+//! 盤面の石の配置に対する完全なランキング
+//!
+//! `m` 個の穴に置かれた石 `x_0, ..., x_{m-1}` を、
+//! `c_i = x_0 + ... + x_i + i` という狭義単調増加列に対応させ、
+//! 組み合わせ数体系 `Σ C(c_i, i + 1)` で整数にする。
+//! 石の合計が `n` 以下の配置はちょうど `0..C(n + m, m)` に隙間なく並ぶので、
+//! 石の数が少ない盤面ほど小さい番号になり、初期の石の数によらず同じ番号が振られる。
+
+/// 片側の穴の数の最大値
+pub const MAX_PITS: usize = 8;
+
+const MAX_HOLES: usize = 2 * MAX_PITS;
+const MAX_N: usize = 255 + MAX_HOLES;
+
+/// `BINOMIAL[n][k] = C(n, k)`。`u64` に収まらないところは `u64::MAX` で止める
+static BINOMIAL: [[u64; MAX_HOLES + 1]; MAX_N + 1] = binomial_table();
+
+const fn binomial_table() -> [[u64; MAX_HOLES + 1]; MAX_N + 1] {
+    let mut table = [[0u64; MAX_HOLES + 1]; MAX_N + 1];
+    let mut n = 0;
+    while n <= MAX_N {
+        table[n][0] = 1;
+        let mut k = 1;
+        while k <= MAX_HOLES && k <= n {
+            table[n][k] = table[n - 1][k - 1].saturating_add(table[n - 1][k]);
+            k += 1;
+        }
+        n += 1;
+    }
+    table
+}
+
+/// `C(n, k)`。`u64` に収まらなければ `None`
+pub fn binomial(n: usize, k: usize) -> Option<u64> {
+    if k > n {
+        return Some(0);
+    }
+    let k = k.min(n - k);
+    let mut c: u128 = 1;
+    for i in 0..k {
+        c = c * (n - i) as u128 / (i + 1) as u128;
+        if c > u128::from(u64::MAX) {
+            return None;
+        }
+    }
+    Some(c as u64)
+}
+
+/// 片側 `pits` 個の穴に合計 `seeds` 個以下の石を置く配置の数
+///
+/// `rank` の値は必ずこれより小さくなる。`u64` に収まらなければ `None`
+pub fn index_size(pits: usize, seeds: usize) -> Option<u64> {
+    binomial(seeds + 2 * pits, 2 * pits)
+}
+
+/// 石の配置を番号にする
+///
+/// 穴の数は `2 * MAX_PITS` 以下、石の合計は `index_size` が `u64` に収まる範囲であること
+pub fn rank<I>(holes: I) -> u64
+where
+    I: IntoIterator<Item = u8>,
+{
+    let mut c = 0;
+    let mut index = 0;
+    for (i, s) in holes.into_iter().enumerate() {
+        c += usize::from(s);
+        index += BINOMIAL[c][i + 1];
+        c += 1;
+    }
+    index
+}
+
+/// `rank` の逆変換
+pub fn unrank(mut index: u64, holes: usize) -> Vec<u8> {
+    let mut c = vec![0; holes];
+    for i in (0..holes).rev() {
+        let k = i + 1;
+        // C(n, k) <= index となる最大の n を探す
+        let mut n = k - 1;
+        while BINOMIAL[n + 1][k] <= index {
+            n += 1;
+        }
+        index -= BINOMIAL[n][k];
+        c[i] = n;
+    }
+    let mut prev = 0;
+    c.iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            let s = c - prev - i;
+            prev = c - i;
+            s as u8
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn binomial_small() {
+        assert_eq!(binomial(5, 2), Some(10));
+        assert_eq!(binomial(2, 5), Some(0));
+        assert_eq!(binomial(36, 12), Some(1_251_677_700));
+        assert_eq!(binomial(144, 16), None);
+        assert_eq!(BINOMIAL[36][12], 1_251_677_700);
+    }
+
+    #[test]
+    fn index_size_small() {
+        assert_eq!(index_size(1, 0), Some(1));
+        assert_eq!(index_size(1, 2), Some(6));
+        assert_eq!(index_size(2, 4), Some(70));
+        assert_eq!(index_size(8, 8 * 2 * 8), None);
+    }
+
+    /// 石の合計が `seeds` 以下の配置を全て列挙する
+    fn all_holes(holes: usize, seeds: u8) -> Vec<Vec<u8>> {
+        if holes == 0 {
+            return vec![vec![]];
+        }
+        let mut list = Vec::new();
+        for s in 0..=seeds {
+            for mut rest in all_holes(holes - 1, seeds - s) {
+                rest.insert(0, s);
+                list.push(rest);
+            }
+        }
+        list
+    }
+
+    #[test]
+    fn dense() {
+        for &(pits, seeds) in &[(1, 6), (2, 4), (3, 3)] {
+            let size = index_size(pits, seeds).unwrap();
+            let mut seen = HashSet::new();
+            for holes in all_holes(2 * pits, seeds as u8) {
+                let index = rank(holes.iter().cloned());
+                assert!(index < size);
+                assert!(seen.insert(index));
+                assert_eq!(unrank(index, 2 * pits), holes);
+            }
+            assert_eq!(seen.len() as u64, size);
+        }
+    }
+
+    #[test]
+    fn ordered_by_seeds() {
+        // 石の合計が n の配置は C(n - 1 + m, m) から始まる
+        assert_eq!(rank(vec![0, 0, 0, 0]), 0);
+        assert_eq!(rank(vec![0, 0, 0, 1]), index_size(2, 0).unwrap());
+        assert_eq!(rank(vec![1, 0, 0, 0]), index_size(2, 1).unwrap() - 1);
+        assert_eq!(rank(vec![0, 0, 0, 2]), index_size(2, 1).unwrap());
+        assert_eq!(rank(vec![2, 0, 0, 0]), index_size(2, 2).unwrap() - 1);
+    }
+
+    #[test]
+    fn large_pits() {
+        let holes = vec![0, 40, 0, 3, 0, 0, 1, 2, 9, 0, 0, 0, 5, 0, 0, 4];
+        assert_eq!(unrank(rank(holes.iter().cloned()), 16), holes);
+    }
+}
@@ -0,0 +1,429 @@
+//! 棋譜
+//!
+//! `[Pits "3"]` のようなタグを1行に1つずつ書き、空行の後に手番ごとに蒔いた穴の添字を書く。
+//! もう一度の手番で続けて蒔いた穴は `0,1` のように `,` でつないで1つの手番にする。
+//! 手番の前の `1.` のような番号は読むときには無視する。
+//!
+//! ```text
+//! [Pits "3"]
+//! [Seeds "3"]
+//! [Stealing "true"]
+//! [Rules "0"]
+//! [Win "score"]
+//! [Allocation "owner"]
+//! [First "alice"]
+//! [Second "bob"]
+//! [Result "8-10"]
+//!
+//! 1. 0,1
+//! 2. 2
+//! ```
+//!
+//! `Rules` は `Ruleset::code` の番号、`Win` と `Allocation` は `--win` と `--allocation` と同じ名前で書く。
+//! 初期配置以外から始めた対局は `[Position "3,0,4/1,2,3 5-0 S"]` で最初の盤面を書く。
+//! 複数の棋譜を続けて書いてもよく、手の後にタグがあれば次の棋譜とする。
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Allocation, Game, Notation, Position, Ruleset, Side, WinCondition};
+
+/// 対局の設定、対局者、結果と、手番ごとに蒔いた穴
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Record {
+    pub pits: usize,
+    pub seeds: usize,
+    pub stealing: bool,
+    /// ルールの番号。`Ruleset::code` と同じもの
+    pub rules: u8,
+    pub win: WinCondition,
+    pub allocation: Allocation,
+    pub first: Option<String>,
+    pub second: Option<String>,
+    /// 終了した対局の先手と後手のスコア
+    pub result: Option<(u8, u8)>,
+    /// 初期配置以外から始めた場合の最初の盤面
+    pub position: Option<Notation>,
+    pub turns: Vec<Vec<usize>>,
+}
+
+/// 初期配置か
+fn is_initial<B: Position>(board: &B) -> bool {
+    let (_, seeds, _) = board.triple();
+    board.side() == Side::First
+        && board.stores() == [0, 0]
+        && [Side::First, Side::Second]
+            .iter()
+            .all(|side| board.pits(*side).iter().all(|s| usize::from(*s) == seeds))
+}
+
+impl Record {
+    /// `game` の最初の盤面から今の盤面までの棋譜。対局者は空にする
+    pub fn from_game<B: Position>(game: &Game<B>) -> Record {
+        let root = game.root();
+        let (pits, seeds, stealing) = root.triple();
+        let board = game.board();
+        Record {
+            pits,
+            seeds,
+            stealing,
+            rules: root.rules().code(),
+            win: root.win_condition(),
+            allocation: root.allocation(),
+            first: None,
+            second: None,
+            result: if board.is_finished() {
+                Some(board.scores())
+            } else {
+                None
+            },
+            position: if is_initial(root) {
+                None
+            } else {
+                Some(root.notation())
+            },
+            turns: game.turns().into_iter().map(|(_, turn)| turn).collect(),
+        }
+    }
+
+    /// `board` と同じ設定で棋譜の手を蒔き直す
+    ///
+    /// 設定が違うか、蒔けない手やもう一度の手番と合わない区切り、盤面と合わない結果があればエラー。
+    /// 結果のない棋譜の最後の手番だけは、もう一度の手番の途中で終わっていてもよい
+    pub fn replay<B: Position>(&self, board: &B) -> Result<Game<B>, String> {
+        let (pits, seeds, stealing) = board.triple();
+        let rules = board.rules().code();
+        let win = board.win_condition();
+        let allocation = board.allocation();
+        if (pits, seeds, stealing) != (self.pits, self.seeds, self.stealing)
+            || rules != self.rules
+            || win != self.win
+            || allocation != self.allocation
+        {
+            return Err(format!(
+                "盤面の設定が違います: record=({}, {}, {}, {}, {}, {}) board=({}, {}, {}, {}, {}, {})",
+                self.pits,
+                self.seeds,
+                self.stealing,
+                self.rules,
+                self.win,
+                self.allocation,
+                pits,
+                seeds,
+                stealing,
+                rules,
+                win,
+                allocation
+            ));
+        }
+        let root = match self.position {
+            Some(ref position) => board.with_notation(position)?,
+            None => board.clone(),
+        };
+        let mut game = Game::new(root);
+        for (i, turn) in self.turns.iter().enumerate() {
+            if turn.is_empty() {
+                return Err(format!("{}手番目: 蒔いた穴がありません", i + 1));
+            }
+            let side = game.board().side();
+            for (j, pos) in turn.iter().enumerate() {
+                if j > 0 && game.board().side() != side {
+                    return Err(format!("{}手番目: もう一度の手番ではありません", i + 1));
+                }
+                game.play(*pos)
+                    .map_err(|e| format!("{}手番目: {}", i + 1, e))?;
+            }
+            // 途中で保存した対局の最後の手番は、もう一度の手番の途中でもよい
+            let last = i + 1 == self.turns.len() && self.result.is_none();
+            if !last && game.board().side() == side && !game.board().is_finished() {
+                return Err(format!("{}手番目: 手番が終わっていません", i + 1));
+            }
+        }
+        if let Some(result) = self.result {
+            if !game.board().is_finished() {
+                return Err("結果がありますが対局が終わっていません".to_string());
+            }
+            if game.board().scores() != result {
+                return Err(format!(
+                    "結果が盤面と合いません: record={:?} board={:?}",
+                    result,
+                    game.board().scores()
+                ));
+            }
+        }
+        Ok(game)
+    }
+}
+
+fn parse_tag(line: &str) -> Result<(String, String), String> {
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| format!("タグは `[名前 \"値\"]` の形で書いてください: {}", line))?;
+    let (name, value) = inner
+        .split_once(' ')
+        .ok_or_else(|| format!("タグに値がありません: {}", line))?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| format!("タグの値は `\"` で囲んでください: {}", line))?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// 1つの手番で続けて蒔いた穴を `0,1` の形にする
+pub fn format_turn(turn: &[usize]) -> String {
+    turn.iter()
+        .map(|pos| pos.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn parse_turn(s: &str) -> Result<Vec<usize>, String> {
+    s.split(',')
+        .map(|pos| {
+            pos.parse()
+                .map_err(|_| format!("穴の添字ではありません: {}", pos))
+        })
+        .collect()
+}
+
+fn parse_result(s: &str) -> Result<Option<(u8, u8)>, String> {
+    if s == "*" {
+        return Ok(None);
+    }
+    let (first, second) = s
+        .split_once('-')
+        .ok_or_else(|| format!("結果は `先手-後手` か `*` で書いてください: {}", s))?;
+    let parse = |v: &str| {
+        v.parse::<u8>()
+            .map_err(|_| format!("スコアではありません: {}", v))
+    };
+    Ok(Some((parse(first)?, parse(second)?)))
+}
+
+fn build(tags: &[(String, String)], turns: Vec<Vec<usize>>) -> Result<Record, String> {
+    let get = |name: &str| {
+        tags.iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    };
+    let require = |name: &str| get(name).ok_or_else(|| format!("{} タグがありません", name));
+    let number = |name: &str| {
+        require(name)?
+            .parse::<usize>()
+            .map_err(|_| format!("{} タグの値が数ではありません", name))
+    };
+    let stealing = match require("Stealing")? {
+        "true" => true,
+        "false" => false,
+        v => {
+            return Err(format!(
+                "Stealing タグは true か false にしてください: {}",
+                v
+            ))
+        }
+    };
+    Ok(Record {
+        pits: number("Pits")?,
+        seeds: number("Seeds")?,
+        stealing,
+        rules: require("Rules")?
+            .parse()
+            .map_err(|_| "Rules タグの値が数ではありません".to_string())?,
+        win: require("Win")?.parse()?,
+        allocation: require("Allocation")?.parse()?,
+        first: get("First").map(str::to_string),
+        second: get("Second").map(str::to_string),
+        result: match get("Result") {
+            Some(result) => parse_result(result)?,
+            None => None,
+        },
+        position: get("Position").map(str::parse).transpose()?,
+        turns,
+    })
+}
+
+/// 続けて書いた棋譜を全て読む。知らないタグは無視する
+pub fn parse_records(s: &str) -> Result<Vec<Record>, String> {
+    let mut records = Vec::new();
+    let mut tags = Vec::new();
+    let mut turns = Vec::new();
+    for line in s.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if line.starts_with('[') {
+            let tag = parse_tag(line)?;
+            // 手のない棋譜が続いたときは同じタグが出てきたところで区切る
+            if !turns.is_empty() || tags.iter().any(|(name, _)| *name == tag.0) {
+                records.push(build(&tags, turns)?);
+                tags = Vec::new();
+                turns = Vec::new();
+            }
+            tags.push(tag);
+            continue;
+        }
+        for token in line.split_whitespace() {
+            // 手番の番号
+            if token.ends_with('.') {
+                continue;
+            }
+            turns.push(parse_turn(token)?);
+        }
+    }
+    if !tags.is_empty() || !turns.is_empty() {
+        records.push(build(&tags, turns)?);
+    }
+    Ok(records)
+}
+
+impl FromStr for Record {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut records = parse_records(s)?;
+        match records.len() {
+            1 => Ok(records.pop().unwrap()),
+            0 => Err("棋譜がありません".to_string()),
+            n => Err(format!("棋譜が{}個あります", n)),
+        }
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "[Pits \"{}\"]", self.pits)?;
+        writeln!(f, "[Seeds \"{}\"]", self.seeds)?;
+        writeln!(f, "[Stealing \"{}\"]", self.stealing)?;
+        writeln!(f, "[Rules \"{}\"]", self.rules)?;
+        writeln!(f, "[Win \"{}\"]", self.win)?;
+        writeln!(f, "[Allocation \"{}\"]", self.allocation)?;
+        if let Some(ref first) = self.first {
+            writeln!(f, "[First \"{}\"]", first)?;
+        }
+        if let Some(ref second) = self.second {
+            writeln!(f, "[Second \"{}\"]", second)?;
+        }
+        match self.result {
+            Some((first, second)) => writeln!(f, "[Result \"{}-{}\"]", first, second)?,
+            None => writeln!(f, "[Result \"*\"]")?,
+        }
+        if let Some(ref position) = self.position {
+            writeln!(f, "[Position \"{}\"]", position)?;
+        }
+        writeln!(f)?;
+        for (i, turn) in self.turns.iter().enumerate() {
+            writeln!(f, "{}. {}", i + 1, format_turn(turn))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Kalah};
+    use typenum::{U2, U3};
+
+    #[test]
+    fn round_trip() {
+        let mut game = Game::new(Board::<U3, U3>::new(true));
+        let mut board = game.board().clone();
+        // どちらかの石がなくなるまで最も小さい添字の穴を蒔く
+        while !board.is_finished() {
+            let pos = board.movable()[0];
+            board.sow(pos);
+            game.play(pos).unwrap();
+        }
+        let mut record = Record::from_game(&game);
+        record.first = Some("alice".to_string());
+        assert_eq!(record.result, Some(board.scores()));
+        assert_eq!(record.position, None);
+        assert_eq!(record.turns.concat(), game.history());
+
+        let text = record.to_string();
+        assert!(text.starts_with(
+            "[Pits \"3\"]\n[Seeds \"3\"]\n[Stealing \"true\"]\n\
+             [Rules \"0\"]\n[Win \"score\"]\n[Allocation \"owner\"]\n"
+        ));
+        assert!(text.contains("\n1. 0,1\n"));
+        let parsed: Record = text.parse().unwrap();
+        assert_eq!(parsed, record);
+        let replayed = parsed.replay(&Board::<U3, U3>::new(true)).unwrap();
+        assert_eq!(replayed.board(), &board);
+        assert_eq!(replayed.history(), game.history());
+
+        // 続けて書いた棋譜
+        let records = parse_records(&format!("{}\n{}", text, text)).unwrap();
+        assert_eq!(records, vec![record.clone(), record]);
+        let empty = Record::from_game(&Game::new(Board::<U3, U3>::new(true)));
+        let records = parse_records(&format!("{}{}", empty, empty)).unwrap();
+        assert_eq!(records, vec![empty.clone(), empty]);
+    }
+
+    #[test]
+    fn from_position() {
+        let start = Board::<U2, U2>::new(false)
+            .with_notation(&"1,2/1,2 2-0 S".parse().unwrap())
+            .unwrap();
+        let mut game = Game::new(start.clone());
+        game.play(0).unwrap();
+        let record = Record::from_game(&game);
+        assert_eq!(record.position, Some(start.notation()));
+        assert_eq!(record.result, None);
+        let text = record.to_string();
+        assert!(text.contains("[Position \"1,2/1,2 2-0 S\"]"));
+        assert!(text.contains("[Result \"*\"]"));
+        let replayed = text
+            .parse::<Record>()
+            .unwrap()
+            .replay(&Board::<U2, U2>::new(false))
+            .unwrap();
+        assert_eq!(replayed.board(), game.board());
+    }
+
+    #[test]
+    fn parse_error() {
+        let board = Board::<U3, U3>::new(true);
+        let header = "[Pits \"3\"]\n[Seeds \"3\"]\n[Stealing \"true\"]\n\
+                      [Rules \"0\"]\n[Win \"score\"]\n[Allocation \"owner\"]\n\n";
+        assert!("[Pits \"3\"]\n\n1. 0".parse::<Record>().is_err());
+        assert!(format!("{}1. x", header).parse::<Record>().is_err());
+        assert!("[Pits 3]".parse::<Record>().is_err());
+        // ルールのタグがない
+        let short = "[Pits \"3\"]\n[Seeds \"3\"]\n[Stealing \"true\"]\n\n1. 0";
+        assert!(short.parse::<Record>().is_err());
+        // 知らない勝利条件
+        assert!(format!("{}1. 0", header.replace("score", "x"))
+            .parse::<Record>()
+            .is_err());
+        assert!("".parse::<Record>().is_err());
+
+        // 0 の後はもう一度の手番なので区切れない
+        let record: Record = format!("{}1. 0\n2. 1", header).parse().unwrap();
+        assert!(record.replay(&board).is_err());
+        let record: Record = format!("{}1. 0", header).parse().unwrap();
+        assert!(record.replay(&board).is_ok());
+        // 1 の後は相手の手番
+        let record: Record = format!("{}1. 1,0", header).parse().unwrap();
+        assert!(record.replay(&board).is_err());
+        // 設定が違う
+        let record: Record = format!("{}1. 0,1", header).parse().unwrap();
+        assert!(record.replay(&Board::<U3, U3>::new(false)).is_err());
+        assert!(record.replay(&board).is_ok());
+        let relay = Board::<U3, U3>::with_rules(Kalah {
+            relay: true,
+            ..Kalah::new(true)
+        });
+        assert!(record.replay(&relay).is_err());
+        assert!(record
+            .replay(&board.clone().with_win_condition(WinCondition::Empty))
+            .is_err());
+        assert!(record
+            .replay(&board.clone().with_allocation(Allocation::Opponent))
+            .is_err());
+        // 終わっていない対局に結果がある
+        let record: Record = format!("{}1. 0,1", header.replace("\n\n", "\n[Result \"0-0\"]\n\n"))
+            .parse()
+            .unwrap();
+        assert!(record.replay(&board).is_err());
+    }
+}
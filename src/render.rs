@@ -0,0 +1,178 @@
+//! 盤面を文字で描く
+//!
+//! 後手の穴を上の段に右から左へ、先手の穴を下の段に左から右へ並べ、
+//! 後手のストアを左、先手のストアを右に置く。石を蒔く向きに一周する並びになる。
+//!
+//! ```text
+//!        [2] [1] [0]
+//! S        3   3   3
+//!      0               0
+//! F        3   3   3       <
+//!        [0] [1] [2]
+//! ```
+
+use std::fmt::Write;
+
+use crate::{Position, Side};
+
+/// 1マスの幅
+const WIDTH: usize = 4;
+
+/// 描き方の設定
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Renderer {
+    /// 穴の添字を描くか
+    pub indices: bool,
+    /// ストアを描くか
+    pub stores: bool,
+    /// 手番側の段に印を付けるか
+    pub side: bool,
+    /// 端末の色を使うか
+    pub color: bool,
+}
+
+impl Default for Renderer {
+    fn default() -> Renderer {
+        Renderer {
+            indices: true,
+            stores: true,
+            side: true,
+            color: false,
+        }
+    }
+}
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+impl Renderer {
+    fn paint(&self, out: &mut String, style: &str, text: &str) {
+        if self.color {
+            out.push_str(style);
+            out.push_str(text);
+            out.push_str(RESET);
+        } else {
+            out.push_str(text);
+        }
+    }
+
+    /// ストアの列の幅
+    fn store_width(&self) -> usize {
+        if self.stores {
+            WIDTH
+        } else {
+            0
+        }
+    }
+
+    fn indices_line<I: Iterator<Item = usize>>(&self, out: &mut String, indices: I) {
+        let mut line = format!("  {:w$}", "", w = self.store_width());
+        for i in indices {
+            write!(line, "{:>w$}", format!("[{}]", i), w = WIDTH).unwrap();
+        }
+        self.paint(out, DIM, line.trim_end());
+        out.push('\n');
+    }
+
+    fn pits_line<'a, I>(&self, out: &mut String, board: &impl Position, side: Side, pits: I)
+    where
+        I: Iterator<Item = &'a u8>,
+    {
+        let label = match side {
+            Side::First => "F",
+            Side::Second => "S",
+        };
+        let mut line = format!("{} {:w$}", label, "", w = self.store_width());
+        for s in pits {
+            write!(line, "{:>w$}", s, w = WIDTH).unwrap();
+        }
+        let to_move = board.side() == side && !board.is_finished();
+        if self.side && to_move {
+            write!(line, "{:w$}   <", "", w = self.store_width()).unwrap();
+        }
+        if to_move {
+            self.paint(out, BOLD, &line);
+        } else {
+            out.push_str(&line);
+        }
+        out.push('\n');
+    }
+
+    /// 複数行の文字列にする。最後に改行は付けない
+    pub fn render<B: Position>(&self, board: &B) -> String {
+        let len = board.pits(Side::First).len();
+        let mut out = String::new();
+        if self.indices {
+            self.indices_line(&mut out, (0..len).rev());
+        }
+        self.pits_line(
+            &mut out,
+            board,
+            Side::Second,
+            board.pits(Side::Second).iter().rev(),
+        );
+        if self.stores {
+            let stores = board.stores();
+            writeln!(
+                out,
+                "  {:>w$}{:pad$}{:>w$}",
+                stores[1],
+                "",
+                stores[0],
+                w = WIDTH,
+                pad = WIDTH * len
+            )
+            .unwrap();
+        }
+        self.pits_line(&mut out, board, Side::First, board.pits(Side::First).iter());
+        if self.indices {
+            self.indices_line(&mut out, 0..len);
+        }
+        out.pop();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, DynBoard};
+    use typenum::{U2, U3};
+
+    #[test]
+    fn render_default() {
+        let mut board = Board::<U3, U3>::new(true);
+        board.sow(0);
+        let expected = [
+            "       [2] [1] [0]",
+            "S        3   3   3",
+            "     0               1",
+            "F        0   4   4       <",
+            "       [0] [1] [2]",
+        ]
+        .join("\n");
+        assert_eq!(board.to_string(), expected);
+        assert_eq!(DynBoard::new(3, 3, true).to_string().lines().count(), 5);
+    }
+
+    #[test]
+    fn render_options() {
+        let board = Board::<U2, U2>::new(true);
+        let renderer = Renderer {
+            indices: false,
+            stores: false,
+            side: false,
+            color: false,
+        };
+        assert_eq!(renderer.render(&board), "S    2   2\nF    2   2");
+
+        let renderer = Renderer {
+            color: true,
+            ..Renderer::default()
+        };
+        let colored = renderer.render(&board);
+        assert!(colored.contains(BOLD));
+        assert!(colored.contains(RESET));
+    }
+}
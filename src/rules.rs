@@ -0,0 +1,648 @@
+//! 石の蒔き方と取り方のルール
+//!
+//! 盤面は各側の穴とストアを環状に並べたものとして扱う。
+//! 反時計回りでは自分の穴を添字の小さい方から大きい方へ進み、自分のストア、
+//! 相手の穴 (自分の最後の穴の向かいが相手の 0 番)、相手のストアの順に一周する。
+
+use std::fmt::Debug;
+
+use crate::Side;
+
+/// 石を蒔く向き
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Direction {
+    /// 自分の穴を添字の大きい方へ進み、自分のストアに入る
+    CounterClockwise,
+    /// 自分の穴を添字の小さい方へ進み、相手のストアの側から相手の穴に入る
+    ///
+    /// 石がストアを通らずに相手の側に移るので、同じ盤面に戻ることがある
+    Clockwise,
+}
+
+/// 石を置く場所
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Slot {
+    Pit(Side, usize),
+    Store(Side),
+}
+
+impl Slot {
+    /// `direction` に進んだ次の場所。`len` は片側の穴の数
+    pub fn next(self, direction: Direction, len: usize) -> Slot {
+        match (direction, self) {
+            (Direction::CounterClockwise, Slot::Pit(side, i)) if i + 1 < len => {
+                Slot::Pit(side, i + 1)
+            }
+            (Direction::CounterClockwise, Slot::Pit(side, _)) => Slot::Store(side),
+            (Direction::CounterClockwise, Slot::Store(side)) => Slot::Pit(side.turned(), 0),
+            (Direction::Clockwise, Slot::Pit(side, 0)) => Slot::Store(side.turned()),
+            (Direction::Clockwise, Slot::Pit(side, i)) => Slot::Pit(side, i - 1),
+            (Direction::Clockwise, Slot::Store(side)) => Slot::Pit(side, len - 1),
+        }
+    }
+}
+
+/// 向かい側の穴
+#[inline]
+pub fn opposite(side: Side, pos: usize, len: usize) -> (Side, usize) {
+    (side.turned(), len - 1 - pos)
+}
+
+/// 石の蒔き方と取り方
+///
+/// 既定の実装は石取りなしのカラハで、変えたいところだけ上書きすればいい
+pub trait Ruleset: Clone + Debug + Send + Sync {
+    /// DBファイルに記録するルールの番号。ルールごとに違う値にする
+    fn code(&self) -> u8;
+
+    /// 石取りの有無
+    fn stealing(&self) -> bool {
+        false
+    }
+
+    fn direction(&self) -> Direction {
+        Direction::CounterClockwise
+    }
+
+    /// 自分のストアにも石を蒔くか。相手のストアには蒔かない
+    fn sow_into_store(&self) -> bool {
+        true
+    }
+
+    /// 一周して石を取り出した穴に戻ったときに飛ばすか
+    fn skip_origin(&self) -> bool {
+        false
+    }
+
+    /// 最後の石が石のあった穴に入ったら、その穴の石を全て取り出して続けて蒔くか (リレー蒔き)
+    ///
+    /// 同じ状態に戻って終わらなくなったら、そこで蒔くのをやめる
+    fn relay(&self) -> bool {
+        false
+    }
+
+    /// `side` が蒔き終えて最後の石を `last` に置いたときに取る穴
+    fn captured_pits(&self, _pits: [&[u8]; 2], _side: Side, _last: Slot) -> Vec<(Side, usize)> {
+        Vec::new()
+    }
+
+    /// `side` が取った石を入れるストア
+    fn capture_destination(&self, side: Side) -> Side {
+        side
+    }
+
+    /// 最後の石を `last` に置いたときにもう一度 `side` の手番になるか。
+    /// 終了した盤面では呼ばれない
+    fn extra_turn(&self, side: Side, last: Slot) -> bool {
+        last == Slot::Store(side)
+    }
+
+    /// 終了時に各側の穴に `remaining` 個ずつ残っていた石を、それぞれのスコアに何個加えるか
+    fn allocate(&self, remaining: [u8; 2]) -> [u8; 2] {
+        remaining
+    }
+
+    /// 相手の穴が空なら相手の側に石が入る手を選ばなければならないか。
+    /// 選べる手がなければ終了する
+    fn must_feed(&self) -> bool {
+        false
+    }
+
+    /// 同じ盤面に戻ることがあるか
+    ///
+    /// 自分のストアを通らずに相手の側に石が移るルールでは戻ることがある
+    fn may_repeat(&self) -> bool {
+        !self.sow_into_store() || self.direction() == Direction::Clockwise
+    }
+}
+
+/// カラハで最後の石が空だった自分の穴に入ったときの石取り
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Capture {
+    /// 取らない
+    None,
+    /// 向かいの相手の穴に石があれば、その石と最後の石を取る
+    Opposite,
+    /// 向かいの相手の穴が空でも最後の石を取る
+    Always,
+}
+
+impl Capture {
+    /// `--stealing` の有無に対応する石取り
+    pub fn from_stealing(stealing: bool) -> Capture {
+        if stealing {
+            Capture::Opposite
+        } else {
+            Capture::None
+        }
+    }
+}
+
+/// カラハ。最後の石が空だった自分の穴に入ったら、向かいの石と合わせてストアに取る
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct Kalah {
+    pub capture: Capture,
+    /// リレー蒔き
+    pub relay: bool,
+}
+
+impl Kalah {
+    pub fn new(stealing: bool) -> Kalah {
+        Kalah {
+            capture: Capture::from_stealing(stealing),
+            relay: false,
+        }
+    }
+}
+
+impl Ruleset for Kalah {
+    fn code(&self) -> u8 {
+        let capture = match self.capture {
+            Capture::None | Capture::Opposite => 0,
+            Capture::Always => 2,
+        };
+        if self.relay {
+            capture + 4
+        } else {
+            capture
+        }
+    }
+
+    fn stealing(&self) -> bool {
+        self.capture != Capture::None
+    }
+
+    fn relay(&self) -> bool {
+        self.relay
+    }
+
+    fn captured_pits(&self, pits: [&[u8]; 2], side: Side, last: Slot) -> Vec<(Side, usize)> {
+        match last {
+            Slot::Pit(row, pos)
+                if self.stealing() && row == side && pits[row.as_usize()][pos] == 1 =>
+            {
+                let (opposite_side, opposite_pos) = opposite(side, pos, pits[0].len());
+                if pits[opposite_side.as_usize()][opposite_pos] > 0 {
+                    vec![(side, pos), (opposite_side, opposite_pos)]
+                } else if self.capture == Capture::Always {
+                    vec![(side, pos)]
+                } else {
+                    Vec::new()
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// オワリ (アワレ)
+///
+/// ストアには蒔かず、12個以上蒔くときは石を取り出した穴を飛ばす。
+/// 最後の石で相手の穴が2個か3個になったら取り、その手前の相手の穴も2個か3個なら続けて取る。
+/// 相手の石を全て取ることになる場合は取らない (グランドスラム)。
+/// 相手の穴が空なら石を渡す手を選ばなければならず、選べなければ残りの石は手番側のものになる
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct Oware;
+
+impl Ruleset for Oware {
+    fn code(&self) -> u8 {
+        1
+    }
+
+    fn stealing(&self) -> bool {
+        true
+    }
+
+    fn sow_into_store(&self) -> bool {
+        false
+    }
+
+    fn skip_origin(&self) -> bool {
+        true
+    }
+
+    fn captured_pits(&self, pits: [&[u8]; 2], side: Side, last: Slot) -> Vec<(Side, usize)> {
+        let opposite = side.turned();
+        let mut captured = Vec::new();
+        if let Slot::Pit(row, pos) = last {
+            if row == opposite {
+                let row = pits[opposite.as_usize()];
+                for i in (0..=pos).rev() {
+                    if row[i] != 2 && row[i] != 3 {
+                        break;
+                    }
+                    captured.push((opposite, i));
+                }
+                let taken = captured.iter().map(|(_, i)| row[*i]).sum::<u8>();
+                if taken == row.iter().sum::<u8>() {
+                    captured.clear();
+                }
+            }
+        }
+        captured
+    }
+
+    fn must_feed(&self) -> bool {
+        true
+    }
+}
+
+/// 実行時に選ぶルール
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Variant {
+    Kalah(Kalah),
+    Oware(Oware),
+}
+
+macro_rules! delegate {
+    ($self:ident, $rules:ident => $e:expr) => {
+        match $self {
+            Variant::Kalah($rules) => $e,
+            Variant::Oware($rules) => $e,
+        }
+    };
+}
+
+impl Ruleset for Variant {
+    fn code(&self) -> u8 {
+        delegate!(self, r => r.code())
+    }
+
+    fn stealing(&self) -> bool {
+        delegate!(self, r => r.stealing())
+    }
+
+    fn direction(&self) -> Direction {
+        delegate!(self, r => r.direction())
+    }
+
+    fn sow_into_store(&self) -> bool {
+        delegate!(self, r => r.sow_into_store())
+    }
+
+    fn skip_origin(&self) -> bool {
+        delegate!(self, r => r.skip_origin())
+    }
+
+    fn relay(&self) -> bool {
+        delegate!(self, r => r.relay())
+    }
+
+    fn captured_pits(&self, pits: [&[u8]; 2], side: Side, last: Slot) -> Vec<(Side, usize)> {
+        delegate!(self, r => r.captured_pits(pits, side, last))
+    }
+
+    fn capture_destination(&self, side: Side) -> Side {
+        delegate!(self, r => r.capture_destination(side))
+    }
+
+    fn extra_turn(&self, side: Side, last: Slot) -> bool {
+        delegate!(self, r => r.extra_turn(side, last))
+    }
+
+    fn allocate(&self, remaining: [u8; 2]) -> [u8; 2] {
+        delegate!(self, r => r.allocate(remaining))
+    }
+
+    fn must_feed(&self) -> bool {
+        delegate!(self, r => r.must_feed())
+    }
+
+    fn may_repeat(&self) -> bool {
+        delegate!(self, r => r.may_repeat())
+    }
+}
+
+/// `side` の手番で `pos` の穴から `rules` に従って石を蒔き、次の手番を返す
+pub(crate) fn sow_seeds<R: Ruleset>(
+    rules: &R,
+    pits: [&mut [u8]; 2],
+    stores: &mut [u8; 2],
+    side: Side,
+    pos: usize,
+) -> Side {
+    let len = pits[0].len();
+    let origin = Slot::Pit(side, pos);
+    let mut num = pits[side.as_usize()][pos];
+    pits[side.as_usize()][pos] = 0;
+    let mut last = origin;
+    let mut laps = Vec::new();
+    loop {
+        while num > 0 {
+            last = last.next(rules.direction(), len);
+            match last {
+                Slot::Store(s) if s != side || !rules.sow_into_store() => continue,
+                Slot::Pit(..) if last == origin && rules.skip_origin() => continue,
+                Slot::Store(s) => stores[s.as_usize()] += 1,
+                Slot::Pit(s, i) => pits[s.as_usize()][i] += 1,
+            }
+            num -= 1;
+        }
+        match last {
+            Slot::Pit(s, i) if rules.relay() && pits[s.as_usize()][i] > 1 => {
+                let lap = (pits[0].to_vec(), pits[1].to_vec(), *stores, last);
+                if laps.contains(&lap) {
+                    break;
+                }
+                laps.push(lap);
+                num = pits[s.as_usize()][i];
+                pits[s.as_usize()][i] = 0;
+            }
+            _ => break,
+        }
+    }
+    let captured = rules.captured_pits([&*pits[0], &*pits[1]], side, last);
+    let destination = rules.capture_destination(side).as_usize();
+    for (s, i) in captured {
+        stores[destination] += pits[s.as_usize()][i];
+        pits[s.as_usize()][i] = 0;
+    }
+    let finished = pits.iter().any(|pits| pits.iter().all(|s| *s == 0));
+    if !finished && rules.extra_turn(side, last) {
+        side
+    } else {
+        side.turned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        search_alphabeta, search_retrograde, search_score, Board, CompactKey, DynBoard, Position,
+        TranspositionTable,
+    };
+    use typenum::{U1, U2, U3};
+
+    #[test]
+    fn cycle() {
+        for &direction in &[Direction::CounterClockwise, Direction::Clockwise] {
+            let mut slot = Slot::Pit(Side::First, 1);
+            let mut seen = Vec::new();
+            for _ in 0..8 {
+                seen.push(slot);
+                slot = slot.next(direction, 3);
+            }
+            assert_eq!(slot, Slot::Pit(Side::First, 1));
+            seen.sort_by_key(|s| format!("{:?}", s));
+            seen.dedup();
+            assert_eq!(seen.len(), 8);
+        }
+        assert_eq!(
+            Slot::Pit(Side::First, 0).next(Direction::Clockwise, 3),
+            Slot::Store(Side::Second)
+        );
+    }
+
+    /// 時計回りでは相手の穴を回ってから自分のストアに入る
+    #[derive(Debug, Clone)]
+    struct Reversed;
+
+    impl Ruleset for Reversed {
+        fn code(&self) -> u8 {
+            u8::MAX
+        }
+
+        fn direction(&self) -> Direction {
+            Direction::Clockwise
+        }
+    }
+
+    #[test]
+    fn clockwise() {
+        let mut first = [1, 0, 4];
+        let mut second = [0, 0, 0];
+        let mut stores = [0, 0];
+        let next = sow_seeds(
+            &Reversed,
+            [&mut first, &mut second],
+            &mut stores,
+            Side::First,
+            2,
+        );
+        assert_eq!(next, Side::Second);
+        assert_eq!((first, second, stores), ([2, 1, 0], [0, 1, 1], [0, 0]));
+    }
+
+    /// ストアに入っても手番が変わる
+    #[derive(Debug, Clone)]
+    struct NoExtraTurn;
+
+    impl Ruleset for NoExtraTurn {
+        fn code(&self) -> u8 {
+            u8::MAX - 1
+        }
+
+        fn stealing(&self) -> bool {
+            true
+        }
+
+        fn captured_pits(&self, pits: [&[u8]; 2], side: Side, last: Slot) -> Vec<(Side, usize)> {
+            Kalah::new(true).captured_pits(pits, side, last)
+        }
+
+        fn extra_turn(&self, _side: Side, _last: Slot) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn solvers_with_rules() {
+        let board = Board::<U3, U2, _>::with_rules(NoExtraTurn);
+        let key = board.key();
+        let expected = search_score(board.clone(), 2, 16).get(&key);
+        assert_ne!(
+            expected,
+            search_score(Board::<U3, U2>::new(true), 2, 16).get(&key)
+        );
+        assert_eq!(search_retrograde(board.clone(), 2).get(key), expected);
+        let mut tt = TranspositionTable::new(1 << 10);
+        assert_eq!(Some(search_alphabeta(&board, &mut tt)), expected);
+        let dyn_board = DynBoard::with_rules(3, 2, NoExtraTurn);
+        assert_eq!(search_score(dyn_board, 2, 16).get(&key), expected);
+    }
+
+    #[test]
+    fn kalah_capture() {
+        for &(capture, captured) in &[
+            (Capture::None, [0, 0]),
+            (Capture::Opposite, [2, 0]),
+            (Capture::Always, [2, 1]),
+        ] {
+            let board = Board::<U3, U1, _>::with_rules(Kalah {
+                capture,
+                relay: false,
+            });
+            // 向かいに石がある
+            let mut next = board.with_pits(&[1, 0, 1], &[0, 1, 1]);
+            next.sow(0);
+            assert_eq!(next.stores()[0], captured[0]);
+            // 向かいが空
+            let mut next = board.with_pits(&[1, 0, 1], &[1, 0, 1]);
+            next.sow(0);
+            assert_eq!(next.stores()[0], captured[1]);
+            assert_eq!(next.pits(Side::Second), &[1, 0, 1]);
+        }
+        assert_eq!(Kalah::new(false).code(), Kalah::new(true).code());
+        assert_ne!(
+            Kalah::new(true).code(),
+            Kalah {
+                capture: Capture::Always,
+                relay: false,
+            }
+            .code()
+        );
+    }
+
+    #[test]
+    fn kalah_relay() {
+        let relay = Kalah {
+            capture: Capture::None,
+            relay: true,
+        };
+        let board = Board::<U3, U1, _>::with_rules(relay).with_pits(&[1, 1, 1], &[1, 1, 1]);
+        // ストアで終わったらもう一度
+        let mut next = board.clone();
+        next.sow(0);
+        assert_eq!(next.pits(Side::First), &[0, 0, 2]);
+        assert_eq!(next.stores(), [1, 0]);
+        assert_eq!(next.side(), Side::First);
+        // 相手の側でも石のあった穴なら蒔き続ける
+        let mut next = board;
+        next.sow(1);
+        assert_eq!(next.pits(Side::First), &[2, 1, 0]);
+        assert_eq!(next.pits(Side::Second), &[0, 2, 0]);
+        assert_eq!(next.stores(), [1, 0]);
+        assert_eq!(next.side(), Side::Second);
+    }
+
+    /// ストアに蒔かないリレー蒔き。同じ状態に戻って終わらないことがある
+    #[derive(Debug, Clone)]
+    struct RelayRing;
+
+    impl Ruleset for RelayRing {
+        fn code(&self) -> u8 {
+            u8::MAX - 2
+        }
+
+        fn sow_into_store(&self) -> bool {
+            false
+        }
+
+        fn relay(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn relay_stops_on_loop() {
+        let mut board = Board::<U2, U1, _>::with_rules(RelayRing).with_pits(&[1, 2], &[0, 2]);
+        board.sow(1);
+        let seeds = board
+            .pits(Side::First)
+            .iter()
+            .chain(board.pits(Side::Second));
+        assert_eq!(seeds.sum::<u8>(), 5);
+        assert_eq!(board.side(), Side::Second);
+    }
+
+    #[test]
+    fn relay_solvers() {
+        let board = Board::<U3, U2, _>::with_rules(Kalah {
+            capture: Capture::Opposite,
+            relay: true,
+        });
+        let key = board.key();
+        let expected = search_score(board.clone(), 2, 16).get(&key);
+        assert_ne!(
+            expected,
+            search_score(Board::<U3, U2>::new(true), 2, 16).get(&key)
+        );
+        assert_eq!(search_retrograde(board.clone(), 2).get(key), expected);
+        let mut tt = TranspositionTable::new(1 << 12);
+        assert_eq!(Some(search_alphabeta(&board, &mut tt)), expected);
+    }
+
+    fn oware<P, S>(first: &[u8], second: &[u8]) -> Board<P, S, Oware>
+    where
+        P: generic_array::ArrayLength<u8> + Clone,
+        S: typenum::Unsigned + Clone,
+    {
+        Board::with_rules(Oware).with_pits(first, second)
+    }
+
+    #[test]
+    fn oware_capture() {
+        let mut board = oware::<U3, U1>(&[0, 0, 3], &[0, 2, 1]);
+        board.sow(2);
+        assert_eq!(board.pits(Side::First), &[0, 0, 0]);
+        assert_eq!(board.pits(Side::Second), &[1, 0, 0]);
+        assert_eq!(board.stores(), [5, 0]);
+        assert_eq!(board.side(), Side::Second);
+
+        // グランドスラムになるので取らない
+        let mut board = oware::<U3, U1>(&[0, 0, 3], &[1, 2, 1]);
+        board.sow(2);
+        assert_eq!(board.pits(Side::Second), &[2, 3, 2]);
+        assert_eq!(board.stores(), [0, 0]);
+    }
+
+    #[test]
+    fn oware_skip_origin() {
+        let mut board = oware::<U2, U1>(&[5, 0], &[1, 1]);
+        board.sow(0);
+        assert_eq!(board.pits(Side::First), &[0, 2]);
+        assert_eq!(board.pits(Side::Second), &[0, 2]);
+        assert_eq!(board.stores(), [3, 0]);
+    }
+
+    #[test]
+    fn oware_must_feed() {
+        let board = oware::<U2, U1>(&[0, 1], &[0, 0]);
+        assert_eq!(board.movable(), vec![1]);
+        assert!(!board.is_finished());
+        let board = oware::<U2, U1>(&[1, 0], &[0, 0]);
+        assert!(board.movable().is_empty());
+        assert!(board.is_finished());
+        assert!(board.can_sow(0).is_err());
+        // 残りの石は手番側のもの
+        assert_eq!(board.scores(), (1, 0));
+        assert_eq!(board.score(), 1);
+    }
+
+    /// 経路だけを覚えて全ての手順を調べる
+    fn minimax<B: Position + CompactKey<Key = u64>>(board: &B, path: &mut Vec<u64>) -> i8 {
+        if board.is_finished() {
+            return board.score();
+        }
+        let key = board.key();
+        if path.contains(&key) {
+            return board.score();
+        }
+        path.push(key);
+        let best = board
+            .list_next()
+            .iter()
+            .map(|next| -minimax(next, path))
+            .max()
+            .unwrap();
+        path.pop();
+        best
+    }
+
+    #[test]
+    fn oware_search() {
+        let board = Board::<U2, U2, _>::with_rules(Oware);
+        let expected = minimax(&board, &mut Vec::new());
+        assert_eq!(
+            search_score(board.clone(), 1, 16).get(&board.key()),
+            Some(expected)
+        );
+        assert_eq!(
+            search_score(board.clone(), 4, 16).get(&board.key()),
+            Some(expected)
+        );
+        let mut tt = TranspositionTable::new(1 << 10);
+        assert_eq!(search_alphabeta(&board, &mut tt), expected);
+    }
+}
@@ -0,0 +1,199 @@
+//! 1つの盤面の値だけを求める αβ 探索
+//!
+//! 全ての到達可能な盤面を保持する代わりに、大きさを決めた置換表に評価値の上下限を覚えておく。
+//! 置換表が溢れたら古いものから上書きするので、メモリに収まらない盤面でも探索できる。
+
+use crate::{CompactKey, Position, Ruleset};
+
+/// 置換表の1エントリ。評価値はストアの差を除いた値で持つ
+#[derive(Debug, Copy, Clone)]
+struct Entry {
+    key: u64,
+    lower: i8,
+    upper: i8,
+}
+
+/// キーの剰余で場所を決め、衝突したら上書きする置換表
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+}
+
+impl TranspositionTable {
+    pub fn new(size: usize) -> TranspositionTable {
+        assert!(size > 0, "置換表の大きさは1以上にしてください");
+        TranspositionTable {
+            entries: vec![None; size],
+        }
+    }
+
+    /// 埋まっているエントリの数
+    pub fn len(&self) -> usize {
+        self.entries.iter().filter(|e| e.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(&self, key: u64) -> Option<(i8, i8)> {
+        match self.entries[(key % self.entries.len() as u64) as usize] {
+            Some(e) if e.key == key => Some((e.lower, e.upper)),
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, key: u64, lower: i8, upper: i8) {
+        let i = (key % self.entries.len() as u64) as usize;
+        let (lower, upper) = match self.entries[i] {
+            Some(e) if e.key == key => (lower.max(e.lower), upper.min(e.upper)),
+            _ => (lower, upper),
+        };
+        self.entries[i] = Some(Entry { key, lower, upper });
+    }
+}
+
+const INF: i16 = 128;
+
+/// fail-soft の αβ 法で手番側から見た最終的なスコア差を求める
+///
+/// 返り値 `v` は `alpha < v < beta` なら正確な値、`v <= alpha` なら上限、`v >= beta` なら下限。
+/// 同じ盤面に戻ることがあるルールでは、`search_score` と同じく `path` にある盤面に戻ったら終わりにする。
+/// 返り値の `bool` はそれで決まった値か。その値は経路によって変わるので置換表に入れない
+fn alphabeta<B>(
+    tt: &mut TranspositionTable,
+    path: &mut Vec<u64>,
+    board: &B,
+    mut alpha: i16,
+    mut beta: i16,
+) -> (i16, bool)
+where
+    B: Position + CompactKey<Key = u64>,
+{
+    if board.is_finished() {
+        return (i16::from(board.score()), false);
+    }
+    let key = board.key();
+    let repeat = board.rules().may_repeat();
+    if repeat && path.contains(&key) {
+        return (i16::from(board.score()), true);
+    }
+    let base = i16::from(board.store_score());
+    if let Some((lower, upper)) = tt.get(key) {
+        let (lower, upper) = (i16::from(lower) + base, i16::from(upper) + base);
+        if lower >= beta || lower == upper {
+            return (lower, false);
+        }
+        if upper <= alpha {
+            return (upper, false);
+        }
+        alpha = alpha.max(lower);
+        beta = beta.min(upper);
+    }
+    let (alpha0, beta0) = (alpha, beta);
+
+    // ストアを多く増やす手から調べる
+    let mut next_list = board.list_next().into_iter().collect::<Vec<_>>();
+    next_list.sort_by_key(|next| next.store_score());
+
+    if repeat {
+        path.push(key);
+    }
+    let mut best = -INF;
+    let mut on_path = false;
+    for next in next_list.iter() {
+        let (score, hit) = alphabeta(tt, path, next, -beta, -alpha);
+        on_path |= hit;
+        if best < -score {
+            best = -score;
+        }
+        if alpha < best {
+            alpha = best;
+        }
+        if alpha >= beta {
+            break;
+        }
+    }
+
+    if repeat {
+        path.pop();
+    }
+
+    if on_path {
+        return (best, true);
+    }
+    let relative = (best - base) as i8;
+    if best <= alpha0 {
+        tt.insert(key, i8::MIN, relative);
+    } else if best >= beta0 {
+        tt.insert(key, relative, i8::MAX);
+    } else {
+        tt.insert(key, relative, relative);
+    }
+    (best, false)
+}
+
+/// `board` の手番側から見た、お互い最善を尽くしたときの最終的なスコア差
+///
+/// `search_score` の DB と違い、ストアの差も含めた値を返す
+pub fn search_alphabeta<B>(board: &B, tt: &mut TranspositionTable) -> i8
+where
+    B: Position + CompactKey<Key = u64>,
+{
+    alphabeta(tt, &mut Vec::new(), board, -INF, INF).0 as i8
+}
+
+/// `search_alphabeta` と同じ値を幅 0 の窓の探索を繰り返して求める (MTD(f))
+pub fn search_mtdf<B>(board: &B, tt: &mut TranspositionTable, guess: i8) -> i8
+where
+    B: Position + CompactKey<Key = u64>,
+{
+    let mut g = i16::from(guess);
+    let mut lower = -INF;
+    let mut upper = INF;
+    while lower < upper {
+        let beta = if g == lower { g + 1 } else { g };
+        g = alphabeta(tt, &mut Vec::new(), board, beta - 1, beta).0;
+        if g < beta {
+            upper = g;
+        } else {
+            lower = g;
+        }
+    }
+    g as i8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search_score, Board, DynBoard};
+    use typenum::{U2, U3, U4};
+
+    #[test]
+    fn alphabeta_matches_score() {
+        for &stealing in &[true, false] {
+            let board = Board::<U3, U3>::new(stealing);
+            let expected = search_score(board.clone(), 2, 16).get(&board.key());
+            let mut tt = TranspositionTable::new(1 << 12);
+            assert_eq!(Some(search_alphabeta(&board, &mut tt)), expected);
+            let mut tt = TranspositionTable::new(1 << 12);
+            assert_eq!(Some(search_mtdf(&board, &mut tt, 0)), expected);
+        }
+    }
+
+    #[test]
+    fn small_table() {
+        let board = Board::<U4, U2>::new(true);
+        let expected = search_score(board.clone(), 2, 16).get(&board.key());
+        let mut tt = TranspositionTable::new(7);
+        assert_eq!(Some(search_alphabeta(&board, &mut tt)), expected);
+        assert!(tt.len() <= 7);
+    }
+
+    #[test]
+    fn mtdf_dyn() {
+        let board = DynBoard::new(2, 2, false);
+        let expected = search_score(board.clone(), 2, 16).get(&board.key());
+        let mut tt = TranspositionTable::new(1 << 10);
+        assert_eq!(Some(search_mtdf(&board, &mut tt, 10)), expected);
+    }
+}
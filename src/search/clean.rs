@@ -5,11 +5,10 @@ use std::hash::BuildHasherDefault;
 use std::ops::{Add, Neg, Sub};
 
 use fnv::{FnvBuildHasher, FnvHasher};
-use generic_array::ArrayLength;
 use locked_hash::LockedHashMap;
-use typenum::Unsigned;
 
-use crate::{Board, CompactKey};
+use super::parallel::{for_each_task, frontier, TASKS_PER_THREAD};
+use crate::{CompactKey, Position, Ruleset};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Settlement {
@@ -92,45 +91,74 @@ impl Settlement {
     }
 }
 
-type DB<P, S> = LockedHashMap<
-    <Board<P, S> as CompactKey>::Key,
-    Settlement,
-    RandomState,
-    BuildHasherDefault<FnvHasher>,
->;
+/// 終了した `board` の手番側から見た勝敗
+fn settle<B: Position>(board: &B) -> Settlement {
+    match board.score().cmp(&0) {
+        Ordering::Greater => Settlement::Win(0),
+        Ordering::Less => Settlement::Lose(0),
+        Ordering::Equal => Settlement::Draw,
+    }
+}
 
-fn search_worker<P, S>(db: &DB<P, S>, board: Board<P, S>, depth: u8) -> Settlement
+/// `search_clean` の DB のキー
+///
+/// 同じ石の配置でもストアの差で勝敗が変わるので、上位に石の配置の番号、下位8bitに
+/// `store_score` を入れる。早なくしでは `store_score` は常に 0 になる
+pub fn settlement_key<B>(board: &B) -> u128
 where
-    P: ArrayLength<u8> + Clone,
-    S: Unsigned + Clone,
-    Board<P, S>: CompactKey,
-    <Board<P, S> as CompactKey>::Key: Send + Sync + Display,
+    B: Position + CompactKey<Key = u64>,
 {
-    let key = board.key();
+    u128::from(board.key()) << 8 | u128::from(board.store_score() as u8)
+}
+
+type DB = LockedHashMap<u128, Settlement, RandomState, BuildHasherDefault<FnvHasher>>;
+
+/// 同じ盤面に戻ることがあるルールでは、`search_score` と同じく戻ったところで終わりにする。
+/// 返り値の `bool` はそれで決まった値か。その値は経路によって変わるので DB に入れない
+fn search_worker<B>(db: &DB, board: B, depth: u8, path: &mut Vec<u128>) -> (Settlement, bool)
+where
+    B: Position + CompactKey<Key = u64>,
+{
+    let key = settlement_key(&board);
     if let Some(score) = db.get(&key) {
-        return score + depth;
+        return (score + depth, false);
     }
     if board.is_finished() {
-        db.insert(key, Settlement::Lose(0));
-        return Settlement::Lose(depth);
+        let settlement = settle(&board);
+        db.insert(key, settlement);
+        return (settlement + depth, false);
+    }
+    let repeat = board.rules().may_repeat();
+    if repeat {
+        if path.contains(&key) {
+            return (settle(&board) + depth, true);
+        }
+        path.push(key);
     }
     let mut best = Settlement::min();
+    let mut on_path = false;
     for next in board.list_next() {
-        let score = -search_worker(db, next, depth + 1);
-        if best < score {
-            best = score;
+        let (score, hit) = search_worker(db, next, depth + 1, path);
+        on_path |= hit;
+        if best < -score {
+            best = -score;
         }
     }
-    db.insert(key, best - depth);
-    best
+    if repeat {
+        path.pop();
+    }
+    if !on_path {
+        db.insert(key, best - depth);
+    }
+    (best, on_path)
 }
 
-pub fn search_clean<P, S>(board: Board<P, S>, threads: usize, div: usize) -> DB<P, S>
+/// `board` の勝利条件で、お互い最善を尽くした場合の勝敗と終了までの手数を全て求める
+///
+/// DB のキーは `settlement_key` で作る
+pub fn search_clean<B>(board: B, threads: usize, div: usize) -> DB
 where
-    P: ArrayLength<u8> + Clone + Send,
-    S: Unsigned + Clone + Send,
-    Board<P, S>: CompactKey,
-    <Board<P, S> as CompactKey>::Key: Send + Sync + Display,
+    B: Position + CompactKey<Key = u64> + Send,
 {
     let db = LockedHashMap::with_div_and_capacity_and_hasher(
         div,
@@ -138,21 +166,27 @@ where
         RandomState::new(),
         FnvBuildHasher::default(),
     );
-    crossbeam::scope(|scope| {
-        for _ in 0..threads {
-            let board = board.clone();
-            scope.spawn(|_| {
-                search_worker(&db, board, 0);
-            });
-        }
-    })
-    .unwrap();
+    // 盤面の評価値は手数を除いて持つので、途中の盤面から探索しても同じ値になる。
+    // 同じ盤面に戻ることがあるルールでは経路によって値が変わる盤面は DB に入らず、
+    // 分担しても使い回せないので分担しない。初期盤面には経路が空のときの値を入れる
+    if !board.rules().may_repeat() {
+        let tasks = frontier(&board, threads * TASKS_PER_THREAD);
+        for_each_task(tasks, threads, |task| {
+            search_worker(&db, task, 0, &mut Vec::new());
+        });
+    }
+    let key = settlement_key(&board);
+    let (best, on_path) = search_worker(&db, board, 0, &mut Vec::new());
+    if on_path {
+        db.insert(key, best);
+    }
     db
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{search_score, Board, DynBoard, WinCondition};
     use typenum::{U1, U2};
 
     #[test]
@@ -176,8 +210,8 @@ mod tests {
 
     #[test]
     fn search_1_1() {
-        let board = Board::<U1, U1>::new(true);
-        let key = board.key();
+        let board = Board::<U1, U1>::new(true).with_win_condition(WinCondition::Empty);
+        let key = settlement_key(&board);
         let db = search_clean(board, 2, 4);
         assert_eq!(db.len(), 2);
         assert_eq!(db.get(&key), Some(Settlement::Win(1)));
@@ -185,8 +219,8 @@ mod tests {
 
     #[test]
     fn search_1_2() {
-        let board = Board::<U1, U1>::new(true);
-        let key = board.key();
+        let board = Board::<U1, U1>::new(true).with_win_condition(WinCondition::Empty);
+        let key = settlement_key(&board);
         let db = search_clean(board, 2, 4);
         assert_eq!(db.len(), 2);
         assert_eq!(db.get(&key), Some(Settlement::Win(1)));
@@ -194,8 +228,8 @@ mod tests {
 
     #[test]
     fn score_2_1_true() {
-        let board = Board::<U2, U1>::new(true);
-        let key = board.key();
+        let board = Board::<U2, U1>::new(true).with_win_condition(WinCondition::Empty);
+        let key = settlement_key(&board);
         let db = search_clean(board, 4, 16);
         assert_eq!(db.len(), 7);
         assert_eq!(db.get(&key), Some(Settlement::Win(1)));
@@ -203,10 +237,57 @@ mod tests {
 
     #[test]
     fn score_2_1_false() {
-        let board = Board::<U2, U1>::new(false);
-        let key = board.key();
+        let board = Board::<U2, U1>::new(false).with_win_condition(WinCondition::Empty);
+        let key = settlement_key(&board);
         let db = search_clean(board, 4, 16);
         assert_eq!(db.len(), 11);
         assert_eq!(db.get(&key), Some(Settlement::Win(3)));
     }
+
+    #[test]
+    fn score_dyn_2_1_false() {
+        let board = DynBoard::new(2, 1, false).with_win_condition(WinCondition::Empty);
+        let key = settlement_key(&board);
+        let db = search_clean(board, 4, 16);
+        assert_eq!(db.len(), 11);
+        assert_eq!(db.get(&key), Some(Settlement::Win(3)));
+    }
+
+    #[test]
+    fn score_condition() {
+        // スコア性の勝敗は search_score の値の符号と一致する
+        for &(pits, seeds, stealing) in &[(1, 1, true), (1, 2, true), (2, 1, true), (3, 2, false)] {
+            let board = DynBoard::new(pits, seeds, stealing);
+            let score = search_score(board.clone(), 2, 16)
+                .get(&board.key())
+                .unwrap();
+            let db = search_clean(board.clone(), 2, 16);
+            let settlement = db.get(&settlement_key(&board)).unwrap();
+            match score.cmp(&0) {
+                Ordering::Greater => assert!(matches!(settlement, Settlement::Win(_))),
+                Ordering::Less => assert!(matches!(settlement, Settlement::Lose(_))),
+                Ordering::Equal => assert_eq!(settlement, Settlement::Draw),
+            }
+        }
+    }
+
+    #[test]
+    fn draw_1_1() {
+        let board = Board::<U1, U1>::new(true);
+        let db = search_clean(board.clone(), 2, 4);
+        assert_eq!(db.get(&settlement_key(&board)), Some(Settlement::Draw));
+    }
+
+    #[test]
+    fn misere_1_2() {
+        // 手が1通りしかないので、スコア性で負ける側がミゼールでは勝つ
+        let board = Board::<U1, U2>::new(true);
+        let db = search_clean(board.clone(), 2, 4);
+        assert_eq!(db.get(&settlement_key(&board)), Some(Settlement::Lose(1)));
+        let board = board.with_win_condition(WinCondition::Misere);
+        let db = search_clean(board.clone(), 2, 4);
+        assert_eq!(db.get(&settlement_key(&board)), Some(Settlement::Win(1)));
+        let db = search_score(board.clone(), 2, 4);
+        assert_eq!(db.get(&board.key()), Some(2));
+    }
 }
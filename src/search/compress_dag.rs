@@ -1,22 +1,17 @@
 use std::collections::{HashMap, HashSet};
 
-use generic_array::ArrayLength;
-use typenum::Unsigned;
+use crate::{CompactKey, Position};
 
-use crate::{Board, CompactKey};
+type DB<B, V> = HashMap<<B as CompactKey>::Key, V>;
 
-type DB<P, S, V> = HashMap<<Board<P, S> as CompactKey>::Key, V>;
-
-pub fn compress_dag<P, S, V>(root: Board<P, S>, db: &DB<P, S, V>, depth: usize) -> DB<P, S, V>
+pub fn compress_dag<B, V>(root: B, db: &DB<B, V>, depth: usize) -> DB<B, V>
 where
-    P: ArrayLength<u8> + Clone,
-    S: Unsigned + Clone,
+    B: Position + CompactKey,
     V: Clone,
-    Board<P, S>: CompactKey,
 {
     let mut compressed = HashMap::new();
     let mut leaf = vec![root];
-    while leaf.len() > 0 {
+    while !leaf.is_empty() {
         let mut mem = HashSet::new();
         for _ in 0..depth {
             let mut next_list = Vec::new();
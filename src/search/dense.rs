@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI8, Ordering};
+
+use crate::DbLookup;
+
+/// まだ評価していないことを表す値
+const UNKNOWN: i8 = i8::MIN;
+
+/// `CompactKey::Key` をそのまま添字にする評価値の配列
+///
+/// 各要素は1バイトで、ハッシュテーブルのようなキーや空きの分のメモリを使わない。
+/// 複数スレッドから同時に読み書きできる
+pub struct DenseDb {
+    values: Vec<AtomicI8>,
+}
+
+impl DenseDb {
+    /// 添字が `0..size` の全てを未評価にして作る
+    pub fn new(size: usize) -> DenseDb {
+        DenseDb {
+            values: (0..size).map(|_| AtomicI8::new(UNKNOWN)).collect(),
+        }
+    }
+
+    /// 添字の範囲を `size` まで広げる。増えた分は未評価になる
+    pub fn grow(&mut self, size: usize) {
+        let len = self.values.len();
+        if size > len {
+            self.values
+                .extend((len..size).map(|_| AtomicI8::new(UNKNOWN)));
+        }
+    }
+
+    /// 添字の範囲
+    pub fn size(&self) -> usize {
+        self.values.len()
+    }
+
+    /// 評価済みの盤面の数
+    pub fn len(&self) -> usize {
+        self.values
+            .iter()
+            .filter(|v| v.load(Ordering::Relaxed) != UNKNOWN)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, key: u64) -> Option<i8> {
+        match self.values[key as usize].load(Ordering::Relaxed) {
+            UNKNOWN => None,
+            v => Some(v),
+        }
+    }
+
+    pub fn insert(&self, key: u64, value: i8) {
+        debug_assert_ne!(value, UNKNOWN);
+        self.values[key as usize].store(value, Ordering::Relaxed);
+    }
+
+    /// 評価済みの `(キー, 評価値)` を添字の順に返す
+    pub fn iter(&self) -> impl Iterator<Item = (u64, i8)> + '_ {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| match v.load(Ordering::Relaxed) {
+                UNKNOWN => None,
+                v => Some((i as u64, v)),
+            })
+    }
+}
+
+impl From<Vec<i8>> for DenseDb {
+    fn from(values: Vec<i8>) -> Self {
+        DenseDb {
+            values: values.into_iter().map(AtomicI8::new).collect(),
+        }
+    }
+}
+
+impl From<&DenseDb> for Vec<i8> {
+    fn from(db: &DenseDb) -> Self {
+        db.values
+            .iter()
+            .map(|v| v.load(Ordering::Relaxed))
+            .collect()
+    }
+}
+
+impl DbLookup<u64, i8> for DenseDb {
+    fn lookup(&self, key: &u64) -> Option<i8> {
+        self.get(*key)
+    }
+}
+
+impl From<DenseDb> for HashMap<u64, i8> {
+    fn from(db: DenseDb) -> Self {
+        db.iter().collect()
+    }
+}
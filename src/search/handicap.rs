@@ -0,0 +1,92 @@
+//! ハンディキャップ
+//!
+//! 先手の有利を打ち消すため、初期盤面から手番側の石を何個か取り除く。
+//! 取り除く数を変えながら解いて、評価値が 0 に近くなる数を探す。
+
+use crate::Position;
+
+/// `board` の手番側の穴から `handicap` 個の石を取り除いた盤面。負なら相手側から取り除く
+///
+/// 石の多い穴から1個ずつ取り、同じ数ならストアに近い (添字の大きい) 穴から取る。
+/// 取り除く石が足りなければ `None`
+pub fn with_handicap<B: Position>(board: &B, handicap: isize) -> Option<B> {
+    let side = if handicap >= 0 {
+        board.side()
+    } else {
+        board.side().turned()
+    };
+    let mut notation = board.notation();
+    let pits = &mut notation.pits[side.as_usize()];
+    for _ in 0..handicap.unsigned_abs() {
+        let (pos, _) = pits
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| **s > 0)
+            .max_by_key(|(pos, s)| (**s, *pos))?;
+        pits[pos] -= 1;
+    }
+    board.with_notation(&notation).ok()
+}
+
+/// ハンディキャップを 0 から評価値が 0 に近づく向きに1個ずつ変えて解き、
+/// (ハンディキャップ, 手番側から見た評価値) を順に返す
+///
+/// `value` は盤面の手番側から見た評価値を返す。評価値が 0 になるか符号が変わったところか、
+/// 取り除く石がなくなったところで止める
+pub fn search_handicap<B, F>(board: &B, mut value: F) -> Vec<(isize, i8)>
+where
+    B: Position,
+    F: FnMut(&B) -> i8,
+{
+    let first = value(board);
+    let mut results = vec![(0, first)];
+    let step = first.signum() as isize;
+    if step == 0 {
+        return results;
+    }
+    let mut handicap = 0;
+    while let Some(next) = with_handicap(board, handicap + step) {
+        handicap += step;
+        let score = value(&next);
+        results.push((handicap, score));
+        if score.signum() as isize != step {
+            break;
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search_score, Board, CompactKey};
+    use typenum::{U2, U3};
+
+    #[test]
+    fn remove_seeds() {
+        let board = Board::<U3, U2>::new(true);
+        let removed = with_handicap(&board, 2).unwrap();
+        assert_eq!(removed.notation().to_string(), "2,1,1/2,2,2 0-0 F");
+        let removed = with_handicap(&board, -4).unwrap();
+        assert_eq!(removed.notation().to_string(), "2,2,2/1,1,0 0-0 F");
+        assert!(with_handicap(&board, 7).is_none());
+        assert_eq!(with_handicap(&board, 0), Some(board));
+    }
+
+    #[test]
+    fn search_until_sign_changes() {
+        let board = Board::<U3, U2>::new(true);
+        let results = search_handicap(&board, |b| {
+            let key = b.key();
+            search_score(b.clone(), 1, 16).get(&key).unwrap()
+        });
+        assert_eq!(results[0], (0, 4));
+        let (last, score) = *results.last().unwrap();
+        assert!(last > 0);
+        assert!(score <= 0);
+        for &(handicap, score) in &results[1..results.len() - 1] {
+            assert!(handicap > 0);
+            assert!(score > 0);
+        }
+    }
+}
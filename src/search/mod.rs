@@ -1,7 +1,20 @@
+mod alphabeta;
 mod clean;
 mod compress_dag;
+mod dense;
+mod handicap;
+mod parallel;
+mod pie;
+mod retrograde;
 mod score;
+mod tablebase;
 
-pub use clean::{search_clean, Settlement};
+pub use alphabeta::{search_alphabeta, search_mtdf, TranspositionTable};
+pub use clean::{search_clean, settlement_key, Settlement};
 pub use compress_dag::compress_dag;
-pub use score::search_score;
+pub use dense::DenseDb;
+pub use handicap::{search_handicap, with_handicap};
+pub use pie::pie_score;
+pub use retrograde::search_retrograde;
+pub use score::{search_score, search_score_dense, search_score_with_tablebase};
+pub use tablebase::Tablebase;
@@ -0,0 +1,95 @@
+//! 探索をスレッドに分けるための道具
+//!
+//! 全てのスレッドが同じ初期盤面から探索すると同じ部分木を重複して調べるので、
+//! 初期盤面から幅優先で広げた盤面の集まりを作業の単位にして、共有のキューから順に配る。
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use crate::{CompactKey, Position};
+
+/// 1スレッドあたりに配る作業の数の目安
+pub(crate) const TASKS_PER_THREAD: usize = 16;
+
+/// `board` から幅優先で広げていき、終局していない盤面が `size` 個以上になった深さの盤面を返す
+///
+/// 同じ盤面は1つにまとめる。それより浅いところで出てきた盤面も除くので、
+/// 同じ盤面に戻ることがあるルールでも止まる。広げきれなければ最後に得られた深さの盤面を返す
+pub(crate) fn frontier<B>(board: &B, size: usize) -> Vec<B>
+where
+    B: Position + CompactKey,
+{
+    let mut seen = HashSet::new();
+    seen.insert(board.key());
+    let mut level = vec![board.clone()];
+    while level.len() < size {
+        let mut next_level = Vec::new();
+        for board in level.iter() {
+            for next in board.list_next() {
+                if !next.is_finished() && seen.insert(next.key()) {
+                    next_level.push(next);
+                }
+            }
+        }
+        if next_level.is_empty() {
+            break;
+        }
+        level = next_level;
+    }
+    level
+}
+
+/// `tasks` を `threads` 個のスレッドに先頭から順に配って `f` を呼ぶ
+pub(crate) fn for_each_task<T, F>(tasks: Vec<T>, threads: usize, f: F)
+where
+    T: Send,
+    F: Fn(T) + Sync,
+{
+    let tasks = Mutex::new(tasks.into_iter());
+    crossbeam::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|_| loop {
+                let task = tasks.lock().unwrap().next();
+                match task {
+                    Some(task) => f(task),
+                    None => break,
+                }
+            });
+        }
+    })
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Oware};
+    use typenum::{U1, U3};
+
+    #[test]
+    fn frontier_is_unique() {
+        let board = Board::<U3, U3>::new(true);
+        let level = frontier(&board, 32);
+        assert!(level.len() >= 32);
+        let keys = level.iter().map(|b| b.key()).collect::<HashSet<_>>();
+        assert_eq!(keys.len(), level.len());
+
+        // 広げきれない盤面
+        let board = Board::<U1, U1>::new(true);
+        assert_eq!(frontier(&board, 32).len(), 1);
+
+        // 同じ盤面を繰り返す盤面
+        let board = Board::<U1, U1, _>::with_rules(Oware);
+        assert!(frontier(&board, 32).len() <= 2);
+    }
+
+    #[test]
+    fn every_task_once() {
+        let tasks = (0..100).collect::<Vec<_>>();
+        let done = Mutex::new(Vec::new());
+        for_each_task(tasks.clone(), 4, |i| done.lock().unwrap().push(i));
+        let mut done = done.into_inner().unwrap();
+        done.sort();
+        assert_eq!(done, tasks);
+    }
+}
@@ -0,0 +1,77 @@
+//! パイルール
+//!
+//! 先手の最初の手番が終わった後、後手は一度だけ先手と席を入れ替えられる。
+//! 先手は入れ替えられても困らない手を選ぶことになるので、先手の有利を打ち消せる。
+
+use crate::Position;
+
+/// 初期盤面 `board` からパイルールで指したときの、先手から見た評価値
+///
+/// `value` は盤面の手番側から見た評価値で、`Position::score` と同じ向きのもの。
+/// 先手の最初の手番の後の盤面とその次の盤面でしか呼ばない
+pub fn pie_score<B, F>(board: &B, mut value: F) -> i8
+where
+    B: Position,
+    F: FnMut(&B) -> i8,
+{
+    if board.is_finished() {
+        return board.score();
+    }
+    board
+        .list_next()
+        .iter()
+        .map(|first| {
+            let second = if first.is_finished() {
+                value(first)
+            } else {
+                first
+                    .list_next_with_swap()
+                    .keys()
+                    .map(|next| -value(next))
+                    .max()
+                    .unwrap()
+            };
+            -second
+        })
+        .max()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search_score, Board, CompactKey, SWAP};
+    use typenum::{U2, U3};
+
+    #[test]
+    fn swap_move() {
+        let board = Board::<U2, U2>::new(true);
+        let first = board
+            .list_next()
+            .into_iter()
+            .find(|b| !b.is_finished())
+            .unwrap();
+        let next = first.list_next_with_swap();
+        assert_eq!(next.len(), first.list_next_with_pos().len() + 1);
+        assert_eq!(next.get(&first), Some(&vec![SWAP]));
+    }
+
+    #[test]
+    fn pie_score_matches_definition() {
+        for &stealing in &[true, false] {
+            let board = Board::<U3, U2>::new(stealing);
+            let db = search_score(board.clone(), 2, 16);
+            let value = |b: &Board<U3, U2>| b.store_score() + db.get(&b.key()).unwrap();
+            // 後手は入れ替えるかどうか得なほうを選ぶので、先手は評価値の絶対値が小さい手を選ぶ
+            let expected = board
+                .list_next()
+                .iter()
+                .map(|first| -value(first).abs())
+                .max()
+                .unwrap();
+            let pie = pie_score(&board, value);
+            assert_eq!(pie, expected);
+            assert!(pie <= 0);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! 穴に残っている石の数ごとに層に分けて後ろから解く探索
+//!
+//! 石は穴からストアに移るだけで戻らないので、ある層の盤面から1手で行ける盤面は
+//! 同じ層かより小さい層にしかない。同じ層に留まる手は自分の側の中で石を先に送るだけなので、
+//! 層の中の遷移にも循環はない。
+//! 層 0, 1, 2, ... の順に、層の中は未評価の次の盤面を先に解きながら全ての盤面を評価する。
+
+use std::ops::{Range, RangeInclusive};
+
+use super::dense::DenseDb;
+use super::parallel::{for_each_task, TASKS_PER_THREAD};
+use crate::{index_size, unrank, CompactKey, Position, Ruleset};
+
+/// 評価値を `search_score` の DB と同じく「ストアの差を除いた最善のスコア差」で返す。
+/// 次の盤面に未評価のものがあればそのキーを返す
+fn evaluate<B>(db: &DenseDb, board: &B) -> Result<i8, u64>
+where
+    B: Position + CompactKey<Key = u64>,
+{
+    if board.is_finished() {
+        return Ok(board.pit_score());
+    }
+    let mut best = -128;
+    for next in board.list_next() {
+        let key = next.key();
+        match db.get(key) {
+            Some(score) => {
+                let score = -(next.store_score() + score);
+                if best < score {
+                    best = score;
+                }
+            }
+            None => return Err(key),
+        }
+    }
+    // 盤面のストアは空なので補正はいらない
+    Ok(best)
+}
+
+/// `index` 番の配置の盤面
+fn position<B: Position>(board: &B, pits: usize, index: u64) -> B {
+    let holes = unrank(index, 2 * pits);
+    board.with_pits(&holes[..pits], &holes[pits..])
+}
+
+/// 層 `layer` の中の番号が `chunk` の盤面を全て評価する。より小さい層は評価済みであること
+///
+/// 途中で同じ層の `chunk` の外の盤面も評価することがある
+fn solve_chunk<B>(db: &DenseDb, board: &B, pits: usize, layer: &Range<u64>, chunk: Range<u64>)
+where
+    B: Position + CompactKey<Key = u64>,
+{
+    let mut stack = Vec::new();
+    for index in chunk {
+        if db.get(index).is_some() {
+            continue;
+        }
+        stack.push(index);
+        while let Some(&i) = stack.last() {
+            if db.get(i).is_some() {
+                stack.pop();
+                continue;
+            }
+            match evaluate(db, &position(board, pits, i)) {
+                Ok(score) => {
+                    db.insert(i, score);
+                    stack.pop();
+                }
+                Err(missing) => {
+                    debug_assert!(layer.start <= missing && missing < layer.end);
+                    stack.push(missing);
+                }
+            }
+        }
+    }
+}
+
+/// 層を区切って `threads` 個のスレッドで評価する
+///
+/// 2つのスレッドが同じ盤面を評価しても書き込む値は同じなので、結果はスレッド数によらない
+fn solve_layer<B>(db: &DenseDb, board: &B, pits: usize, layer: Range<u64>, threads: usize)
+where
+    B: Position + CompactKey<Key = u64> + Send,
+{
+    let chunk_size = ((layer.end - layer.start) / (threads * TASKS_PER_THREAD) as u64).max(1);
+    let tasks = (layer.start..layer.end)
+        .step_by(chunk_size as usize)
+        .map(|start| (board.clone(), start..layer.end.min(start + chunk_size)))
+        .collect::<Vec<_>>();
+    for_each_task(tasks, threads, |(board, chunk)| {
+        solve_chunk(db, &board, pits, &layer, chunk);
+    });
+}
+
+/// 石の数が `layers` の層を順に評価する。`db` の大きさは足りていること
+pub(crate) fn solve_layers<B>(
+    db: &DenseDb,
+    board: &B,
+    layers: RangeInclusive<usize>,
+    threads: usize,
+) where
+    B: Position + CompactKey<Key = u64> + Send,
+{
+    assert!(
+        !board.rules().may_repeat(),
+        "同じ盤面に戻ることがあるルールには使えません"
+    );
+    let (pits, _, _) = board.triple();
+    let mut start = match *layers.start() {
+        0 => 0,
+        n => index_size(pits, n - 1).unwrap(),
+    };
+    for n in layers {
+        let end = index_size(pits, n).unwrap();
+        solve_layer(db, board, pits, start..end, threads);
+        start = end;
+    }
+}
+
+/// `board` と同じ設定で、石の数が初期の総数以下の全ての配置を評価する
+///
+/// 結果は `search_score_dense` と同じ形式で、到達できない盤面も含む
+pub fn search_retrograde<B>(board: B, threads: usize) -> DenseDb
+where
+    B: Position + CompactKey<Key = u64> + Send,
+{
+    let (pits, seeds, _) = board.triple();
+    let total = 2 * pits * seeds;
+    let size = index_size(pits, total).expect("盤面の番号が64bitに収まりません");
+    let db = DenseDb::new(size as usize);
+    solve_layers(&db, &board, 0..=total, threads);
+    db
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search_score, Allocation, Board, DynBoard, Oware, WinCondition};
+    use typenum::{U2, U3};
+
+    #[test]
+    fn retrograde_matches_score() {
+        let conditions = [
+            WinCondition::Score,
+            WinCondition::Empty,
+            WinCondition::Misere,
+        ];
+        for &stealing in &[true, false] {
+            for &win in conditions.iter() {
+                let board = Board::<U3, U2>::new(stealing).with_win_condition(win);
+                let key = board.key();
+                let db = search_score(board.clone(), 2, 16);
+                let retro = search_retrograde(board, 2);
+                assert_eq!(retro.len(), retro.size());
+                assert_eq!(retro.get(key), db.get(&key));
+                let db: std::collections::HashMap<_, _> = db.into();
+                for (key, score) in db {
+                    assert_eq!(retro.get(key), Some(score));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn retrograde_with_allocation() {
+        for &allocation in &[Allocation::Emptied, Allocation::Opponent] {
+            let board = Board::<U3, U2>::new(true).with_allocation(allocation);
+            let db: std::collections::HashMap<_, _> = search_score(board.clone(), 2, 16).into();
+            let retro = search_retrograde(board, 2);
+            for (key, score) in db {
+                assert_eq!(retro.get(key), Some(score));
+            }
+        }
+    }
+
+    #[test]
+    fn retrograde_dyn() {
+        let board = Board::<U2, U2>::new(true);
+        let dyn_board = DynBoard::new(2, 2, true);
+        let retro = search_retrograde(board, 1);
+        let dyn_retro = search_retrograde(dyn_board, 4);
+        assert_eq!(
+            retro.iter().collect::<Vec<_>>(),
+            dyn_retro.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn reject_repeating_rules() {
+        search_retrograde(Board::<U2, U2, _>::with_rules(Oware), 1);
+    }
+}
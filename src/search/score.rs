@@ -1,49 +1,147 @@
 use std::collections::hash_map::RandomState;
 use std::fmt::Display;
-use std::hash::BuildHasherDefault;
+use std::hash::{BuildHasherDefault, Hash};
 
 use fnv::{FnvBuildHasher, FnvHasher};
-use generic_array::ArrayLength;
 use locked_hash::LockedHashMap;
-use typenum::Unsigned;
 
-use crate::{Board, CompactKey};
+use super::dense::DenseDb;
+use super::parallel::{for_each_task, frontier, TASKS_PER_THREAD};
+use super::tablebase::Tablebase;
+use crate::{index_size, CompactKey, Position, Ruleset};
 
-type DB<P, S> =
-    LockedHashMap<<Board<P, S> as CompactKey>::Key, i8, RandomState, BuildHasherDefault<FnvHasher>>;
+type DB<B> = LockedHashMap<<B as CompactKey>::Key, i8, RandomState, BuildHasherDefault<FnvHasher>>;
 
-fn search_worker<P, S>(db: &DB<P, S>, board: Board<P, S>) -> i8
+/// `search_worker` が評価値を書き込む先
+pub(crate) trait ScoreStore<K>: Sync {
+    fn get(&self, key: &K) -> Option<i8>;
+    fn insert(&self, key: K, score: i8);
+}
+
+impl<K> ScoreStore<K> for LockedHashMap<K, i8, RandomState, BuildHasherDefault<FnvHasher>>
 where
-    P: ArrayLength<u8> + Clone,
-    S: Unsigned + Clone,
-    Board<P, S>: CompactKey,
-    <Board<P, S> as CompactKey>::Key: Send + Sync + Display,
+    K: Hash + Eq + Send + Sync + Display,
 {
+    fn get(&self, key: &K) -> Option<i8> {
+        LockedHashMap::get(self, key)
+    }
+
+    fn insert(&self, key: K, score: i8) {
+        LockedHashMap::insert(self, key, score);
+    }
+}
+
+impl ScoreStore<u64> for DenseDb {
+    fn get(&self, key: &u64) -> Option<i8> {
+        DenseDb::get(self, *key)
+    }
+
+    fn insert(&self, key: u64, score: i8) {
+        DenseDb::insert(self, key, score);
+    }
+}
+
+/// `path` は今辿っている盤面のキー。同じ盤面に戻ることがあるルールでだけ使う
+///
+/// 同じ盤面に戻ったらそこで終わりにして、それぞれ自分の側の石を取る。
+/// 返り値の `bool` は `path` にある盤面に戻ったことで決まった値か。
+/// その値は経路によって変わるので DB に入れず、別の経路で出てきたら解き直す
+fn search_worker<B, D, F>(db: &D, probe: &F, board: B, path: &mut Vec<B::Key>) -> (i8, bool)
+where
+    B: Position + CompactKey,
+    D: ScoreStore<B::Key>,
+    F: Fn(&B) -> Option<i8>,
+{
+    if let Some(score) = probe(&board) {
+        return (board.store_score() + score, false);
+    }
     let key = board.key();
     if let Some(score) = db.get(&key) {
-        return board.store_score() + score;
+        return (board.store_score() + score, false);
     }
     if board.is_finished() {
         db.insert(key, board.pit_score());
-        return board.score();
+        return (board.score(), false);
+    }
+    let repeat = board.rules().may_repeat();
+    if repeat {
+        if path.contains(&key) {
+            return (board.score(), true);
+        }
+        path.push(key.clone());
     }
     let mut best = -128;
+    let mut on_path = false;
     for next in board.list_next() {
-        let score = -search_worker(db, next);
-        if best < score {
-            best = score;
+        let (score, hit) = search_worker(db, probe, next, path);
+        on_path |= hit;
+        if best < -score {
+            best = -score;
         }
     }
-    db.insert(key, best - board.store_score());
-    best
+    if repeat {
+        path.pop();
+    }
+    if !on_path {
+        db.insert(key, best - board.store_score());
+    }
+    (best, on_path)
+}
+
+/// 初期盤面から広げた盤面を `threads` 個のスレッドで分担して解いてから、初期盤面を解く
+///
+/// 各盤面の評価値は探索の順番によらないので、スレッド数を変えても同じ DB になる。
+/// 同じ盤面に戻ることがあるルールでは経路によって値が変わる盤面を DB に入れず、
+/// 分担しても使い回せないので初期盤面から解く。初期盤面には経路が空のときの値を入れる
+fn search_parallel<B, D, F>(db: &D, probe: &F, board: B, threads: usize)
+where
+    B: Position + CompactKey + Send,
+    D: ScoreStore<B::Key>,
+    F: Fn(&B) -> Option<i8> + Sync,
+{
+    if !board.rules().may_repeat() {
+        let tasks = frontier(&board, threads * TASKS_PER_THREAD);
+        for_each_task(tasks, threads, |task| {
+            search_worker(db, probe, task, &mut Vec::new());
+        });
+    }
+    let key = board.key();
+    let store_score = board.store_score();
+    let (best, on_path) = search_worker(db, probe, board, &mut Vec::new());
+    if on_path {
+        db.insert(key, best - store_score);
+    }
+}
+
+pub fn search_score<B>(board: B, threads: usize, div: usize) -> DB<B>
+where
+    B: Position + CompactKey + Send,
+    B::Key: Send + Sync + Display,
+{
+    search_score_with_probe(board, threads, div, |_| None)
 }
 
-pub fn search_score<P, S>(board: Board<P, S>, threads: usize, div: usize) -> DB<P, S>
+/// `search_score` と同じだが、`tablebase` にある盤面はそこから引いて探索しない
+///
+/// 表にある盤面は返り値の DB には入らない
+pub fn search_score_with_tablebase<B>(
+    board: B,
+    threads: usize,
+    div: usize,
+    tablebase: &Tablebase,
+) -> DB<B>
 where
-    P: ArrayLength<u8> + Clone + Send,
-    S: Unsigned + Clone + Send,
-    Board<P, S>: CompactKey,
-    <Board<P, S> as CompactKey>::Key: Send + Sync + Display,
+    B: Position + CompactKey<Key = u64> + Send,
+{
+    assert!(tablebase.is_for(&board), "盤面の設定が違います");
+    search_score_with_probe(board, threads, div, |b| tablebase.probe(b))
+}
+
+fn search_score_with_probe<B, F>(board: B, threads: usize, div: usize, probe: F) -> DB<B>
+where
+    B: Position + CompactKey + Send,
+    B::Key: Send + Sync + Display,
+    F: Fn(&B) -> Option<i8> + Sync,
 {
     let db = LockedHashMap::with_div_and_capacity_and_hasher(
         div,
@@ -51,22 +149,27 @@ where
         RandomState::new(),
         FnvBuildHasher::default(),
     );
-    crossbeam::scope(|scope| {
-        for _ in 0..threads {
-            let board = board.clone();
-            scope.spawn(|_| {
-                search_worker(&db, board);
-            });
-        }
-    })
-    .unwrap();
+    search_parallel(&db, &probe, board, threads);
+    db
+}
+
+/// `search_score` と同じ探索を、盤面の番号を添字にした配列の上で行う
+pub fn search_score_dense<B>(board: B, threads: usize) -> DenseDb
+where
+    B: Position + CompactKey<Key = u64> + Send,
+{
+    let (pits, seeds, _) = board.triple();
+    let size = index_size(pits, 2 * pits * seeds).expect("盤面の番号が64bitに収まりません");
+    let db = DenseDb::new(size as usize);
+    search_parallel(&db, &|_: &B| None, board, threads);
     db
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use typenum::{U1, U2};
+    use crate::{Board, DynBoard};
+    use typenum::{U1, U2, U3};
 
     #[test]
     fn score_1_1() {
@@ -94,4 +197,61 @@ mod tests {
         assert_eq!(db.len(), 7);
         assert_eq!(db.get(&key), Some(2));
     }
+
+    #[test]
+    fn score_dyn_2_1() {
+        let board = DynBoard::new(2, 1, true);
+        let key = board.key();
+        let db = search_score(board, 4, 16);
+        assert_eq!(db.len(), 7);
+        assert_eq!(db.get(&key), Some(2));
+    }
+
+    #[test]
+    fn score_dyn_matches_typed() {
+        let board = Board::<U3, U2>::new(true);
+        let dyn_board = DynBoard::new(3, 2, true);
+        let key = board.key();
+        let dyn_key = dyn_board.key();
+        let db = search_score(board, 4, 16);
+        let dyn_db = search_score(dyn_board, 4, 16);
+        assert_eq!(db.len(), dyn_db.len());
+        assert_eq!(db.get(&key), dyn_db.get(&dyn_key));
+    }
+
+    #[test]
+    fn same_for_any_threads() {
+        let board = Board::<U3, U2>::new(false);
+        let single: std::collections::HashMap<_, _> = search_score(board.clone(), 1, 16).into();
+        for &threads in &[2, 3, 8] {
+            let db: std::collections::HashMap<_, _> =
+                search_score(board.clone(), threads, 16).into();
+            assert_eq!(db, single);
+        }
+    }
+
+    #[test]
+    fn dense_matches_hash() {
+        let board = Board::<U3, U2>::new(true);
+        let key = board.key();
+        let db = search_score(board.clone(), 4, 16);
+        let dense = search_score_dense(board, 4);
+        assert_eq!(dense.size(), 18564);
+        assert_eq!(dense.len(), db.len());
+        assert_eq!(dense.get(key), db.get(&key));
+        for (key, score) in dense.iter() {
+            assert_eq!(db.get(&key), Some(score));
+        }
+    }
+
+    #[test]
+    fn with_tablebase() {
+        let board = Board::<U3, U2>::new(true);
+        let key = board.key();
+        let db = search_score(board.clone(), 2, 16);
+        let tablebase = Tablebase::build(&board, 6, 2);
+        let probed = search_score_with_tablebase(board, 2, 16, &tablebase);
+        assert!(probed.len() < db.len());
+        assert_eq!(probed.get(&key), db.get(&key));
+    }
 }
@@ -0,0 +1,225 @@
+//! 石の数が少ない盤面の評価値をまとめた表
+//!
+//! 盤面の番号は石の合計が少ない順に振られていて初期の石の数によらないので、
+//! 「穴に残っている石が `seeds` 個以下の全ての盤面」の評価値は同じ穴の数・ルールなら
+//! どの初期配置の探索でも共通に使える。
+
+use std::io::{self, Read, Write};
+
+use super::dense::DenseDb;
+use super::retrograde::solve_layers;
+use crate::db::{checksum, invalid_data};
+use crate::{
+    index_size, Allocation, CompactKey, DbKind, Header, Position, Ruleset, WinCondition,
+    HEADER_SIZE,
+};
+
+pub struct Tablebase {
+    pits: usize,
+    seeds: usize,
+    stealing: bool,
+    win: WinCondition,
+    rules: u8,
+    allocation: Allocation,
+    db: DenseDb,
+}
+
+impl Tablebase {
+    /// `board` と同じ穴の数とルールで、穴に残っている石が `seeds` 個以下の盤面を全て解く
+    pub fn build<B>(board: &B, seeds: usize, threads: usize) -> Tablebase
+    where
+        B: Position + CompactKey<Key = u64> + Send,
+    {
+        let (pits, _, stealing) = board.triple();
+        assert!(
+            seeds <= i8::MAX as usize,
+            "石の数は{}以下にしてください",
+            i8::MAX
+        );
+        let size = index_size(pits, seeds).expect("盤面の番号が64bitに収まりません");
+        let db = DenseDb::new(size as usize);
+        solve_layers(&db, board, 0..=seeds, threads);
+        Tablebase {
+            pits,
+            seeds,
+            stealing,
+            win: board.win_condition(),
+            rules: board.rules().code(),
+            allocation: board.allocation(),
+            db,
+        }
+    }
+
+    /// 解いてある層はそのまま使い、石が `seeds` 個以下になるまで広げる
+    pub fn extend<B>(&mut self, board: &B, seeds: usize, threads: usize)
+    where
+        B: Position + CompactKey<Key = u64> + Send,
+    {
+        assert!(self.is_for(board), "盤面の設定が違います");
+        if seeds <= self.seeds {
+            return;
+        }
+        assert!(
+            seeds <= i8::MAX as usize,
+            "石の数は{}以下にしてください",
+            i8::MAX
+        );
+        let size = index_size(self.pits, seeds).expect("盤面の番号が64bitに収まりません");
+        self.db.grow(size as usize);
+        solve_layers(&self.db, board, self.seeds + 1..=seeds, threads);
+        self.seeds = seeds;
+    }
+
+    pub fn pits(&self) -> usize {
+        self.pits
+    }
+
+    /// 穴に残っている石の最大数
+    pub fn seeds(&self) -> usize {
+        self.seeds
+    }
+
+    pub fn stealing(&self) -> bool {
+        self.stealing
+    }
+
+    pub fn win_condition(&self) -> WinCondition {
+        self.win
+    }
+
+    /// `board` の探索に使える表か
+    pub fn is_for<B: Position>(&self, board: &B) -> bool {
+        let (pits, _, stealing) = board.triple();
+        self.pits == pits
+            && self.stealing == stealing
+            && self.win == board.win_condition()
+            && self.rules == board.rules().code()
+            && self.allocation == board.allocation()
+    }
+
+    /// 表にある盤面なら、ストアの差を除いた最善のスコア差を返す
+    pub fn probe<B>(&self, board: &B) -> Option<i8>
+    where
+        B: Position + CompactKey<Key = u64>,
+    {
+        let on_board = board.self_pits().iter().chain(board.opposite_pits());
+        if on_board.map(|s| usize::from(*s)).sum::<usize>() > self.seeds {
+            return None;
+        }
+        self.db.get(board.key())
+    }
+
+    /// `Header` の後に評価値を番号順に1バイトずつ並べて書き出す
+    pub fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let body = Vec::from(&self.db)
+            .into_iter()
+            .map(|v| v as u8)
+            .collect::<Vec<_>>();
+        let header = Header {
+            kind: DbKind::Tablebase,
+            key_width: 0,
+            value_width: 1,
+            pits: self.pits as u8,
+            seeds: self.seeds as u8,
+            stealing: self.stealing,
+            win: self.win,
+            rules: self.rules,
+            allocation: self.allocation,
+            len: body.len() as u64,
+            checksum: checksum(&body),
+        };
+        writer.write_all(&header.to_bytes())?;
+        writer.write_all(&body)?;
+        writer.flush()
+    }
+
+    pub fn load<R: Read>(mut reader: R) -> io::Result<Tablebase> {
+        let mut buf = [0; HEADER_SIZE];
+        reader.read_exact(&mut buf)?;
+        let header = Header::from_bytes(&buf)?;
+        if header.kind != DbKind::Tablebase {
+            return Err(invalid_data(format!(
+                "DBの種類が違います: {:?}",
+                header.kind
+            )));
+        }
+        let pits = usize::from(header.pits);
+        let seeds = usize::from(header.seeds);
+        if index_size(pits, seeds) != Some(header.len) {
+            return Err(invalid_data("エントリ数が合いません".to_string()));
+        }
+        let mut body = Vec::with_capacity(header.len as usize);
+        reader.read_to_end(&mut body)?;
+        if body.len() as u64 != header.len {
+            return Err(invalid_data("エントリ数が合いません".to_string()));
+        }
+        if checksum(&body) != header.checksum {
+            return Err(invalid_data("チェックサムが一致しません".to_string()));
+        }
+        let values = body.into_iter().map(|v| v as i8).collect::<Vec<_>>();
+        Ok(Tablebase {
+            pits,
+            seeds,
+            stealing: header.stealing,
+            win: header.win,
+            rules: header.rules,
+            allocation: header.allocation,
+            db: DenseDb::from(values),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search_retrograde, Board};
+    use typenum::{U1, U2, U3};
+
+    #[test]
+    #[should_panic]
+    fn reject_too_many_seeds() {
+        Tablebase::build(&Board::<U1, U1>::new(true), 150, 1);
+    }
+
+    #[test]
+    fn shared_between_seeds() {
+        let tb = Tablebase::build(&Board::<U2, U1>::new(true), 4, 2);
+        assert_eq!(tb.seeds(), 4);
+        let full = search_retrograde(Board::<U2, U2>::new(true), 2);
+        for (key, score) in full.iter().take(tb.db.size()) {
+            assert_eq!(tb.db.get(key), Some(score));
+        }
+        let board = Board::<U2, U2>::new(true);
+        assert_eq!(tb.probe(&board), None);
+        let tb = Tablebase::build(&board, 8, 2);
+        assert_eq!(tb.probe(&board), full.get(board.key()));
+    }
+
+    #[test]
+    fn extend() {
+        let board = Board::<U3, U1>::new(false);
+        let mut tb = Tablebase::build(&board, 2, 1);
+        tb.extend(&board, 5, 4);
+        let full = Tablebase::build(&board, 5, 1);
+        assert_eq!(Vec::from(&tb.db), Vec::from(&full.db));
+        assert!(!tb.is_for(&Board::<U3, U1>::new(true)));
+        assert!(!tb.is_for(&Board::<U1, U1>::new(false)));
+        assert!(!tb.is_for(&board.with_win_condition(WinCondition::Empty)));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let board = Board::<U2, U1>::new(true);
+        let tb = Tablebase::build(&board, 5, 2);
+        let mut buf = Vec::new();
+        tb.save(&mut buf).unwrap();
+        let loaded = Tablebase::load(buf.as_slice()).unwrap();
+        assert_eq!(loaded.pits(), 2);
+        assert_eq!(loaded.seeds(), 5);
+        assert!(loaded.stealing());
+        assert_eq!(Vec::from(&loaded.db), Vec::from(&tb.db));
+
+        *buf.last_mut().unwrap() ^= 1;
+        assert!(Tablebase::load(buf.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use std::process::Command;
+
+/// `mancala` を実行して標準出力を返す
+fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_mancala"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// `key=` の値
+fn value<'a>(output: &'a str, key: &str) -> &'a str {
+    output
+        .split_whitespace()
+        .find_map(|token| token.strip_prefix(key))
+        .unwrap()
+}
+
+#[test]
+fn score_includes_stores() {
+    let position = ["--position", "1,1/1,1 3-0 F"];
+    let alphabeta = run(&[&["score", "--alphabeta"][..], &position].concat());
+    assert_eq!(value(&alphabeta, "score="), "3");
+    let tablebase = std::env::temp_dir().join(format!("cli-{}.tb", std::process::id()));
+    let tablebase = tablebase.to_str().unwrap();
+    run(&[
+        "tablebase",
+        "--pits",
+        "2",
+        "--tb-seeds",
+        "2",
+        "--output",
+        tablebase,
+    ]);
+    for options in &[
+        &[][..],
+        &["--dense"],
+        &["--retrograde"],
+        &["--tablebase", tablebase],
+    ] {
+        let output = run(&[&["score"][..], options, &position].concat());
+        assert_eq!(value(&output, "score="), "3", "{:?}", options);
+    }
+    std::fs::remove_file(tablebase).unwrap();
+}
+
+#[test]
+fn handicap_includes_stores() {
+    let args = ["handicap", "--position", "2,1/1,1 3-0 F"];
+    let alphabeta = run(&[&args[..], &["--alphabeta"]].concat());
+    assert!(alphabeta.contains("handicap=0 score="));
+    assert_eq!(run(&args), alphabeta);
+}
+
+#[test]
+fn solve_empty_with_capture() {
+    // 石を取って相手の側をなくした手も、終了させた側の勝ちとして数える
+    let output = run(&["solve", "--pits", "3", "--seeds", "3", "--stealing"]);
+    assert_eq!(value(&output, "score="), "Win(11)");
+}
@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 
 use generic_array::ArrayLength;
-use mancala_full_search::{Board, CompactKey};
+use mancala_full_search::{Board, CompactKey, Position};
 use typenum::*;
 
 fn calc_key<P, S>(board: &Board<P, S>) -> Vec<u8>